@@ -1,150 +1,35 @@
-use chumsky::prelude::*;
 use proc_macro2::{Ident, Span, TokenStream};
 use quote::quote;
 use std::fs;
 use std::path::PathBuf;
-use steel::compiler::passes::analysis::query_top_level_define;
+use steel::steel_vm::builtin::BuiltInModule;
 use steel::steel_vm::engine::Engine;
-use toml;
 
-fn extract_quoted_string(input: &str) -> &str {
-    let start = input.find('"').unwrap() + 1;
-    let end = input[start..].rfind('"').unwrap() + start;
-    &input[start..end]
+// Shared with the binary, which uses the parts of these the build script does not.
+#[allow(dead_code)]
+#[path = "src/documentation.rs"]
+mod documentation;
+#[allow(dead_code)]
+#[path = "src/params.rs"]
+mod params;
+#[allow(dead_code)]
+#[path = "src/plugin_doc.rs"]
+mod plugin_doc;
+#[allow(dead_code)]
+#[path = "src/wrappers"]
+mod wrappers {
+    pub mod toml;
 }
 
-include!("./src/documentation.rs");
-
-use chumsky::prelude::*;
-
-fn parser<'a>() -> impl Parser<'a, &'a str, Item, extra::Err<Rich<'a, char>>> {
-    let ident = text::ascii::ident().map(|s: &str| s.to_string());
-
-    // rest of line (trimmed), stops before \n
-    let rest_of_line = any()
-        .filter(|c: &char| *c != '\n')
-        .repeated()
-        .collect::<String>()
-        .map(|s| s.trim().to_string());
-
-    // optional {TypeName} — only handles simple Named types for now
-    let ty = ident
-        .clone()
-        .delimited_by(just('{'), just('}'))
-        .padded()
-        .map(TypeExpr::Named)
-        .or_not();
-
-    // The very first non-tag, non-empty line is the summary.
-
-    let summary = rest_of_line
-        .clone()
-        .filter(|s| !s.is_empty() && !s.starts_with('@'));
-
-    // Zero or more non-tag lines after the summary.
-
-    let desc_line = rest_of_line
-        .clone()
-        .filter(|s: &String| !s.starts_with('@'))
-        .then_ignore(just('\n').or_not());
-
-    let description = desc_line
-        .repeated()
-        .at_least(1)
-        .collect::<Vec<_>>()
-        .map(|lines| lines.join("\n"))
-        .map(|s| s.trim().to_string())
-        .or_not();
-
-    let param = just("@param")
-        .then(just(' ').repeated().at_least(1))
-        .ignore_then(ident.clone()) // name
-        .then(ty) // optional {Type}
-        .then_ignore(just(' ').repeated())
-        .then(rest_of_line.clone().or_not()) // description
-        .then_ignore(just('\n').or_not())
-        .map(|((name, param_type), description)| Param {
-            name,
-            param_type,
-            description: description.filter(|s| !s.is_empty()),
-            modifiers: vec![],
-        });
-
-    let ret = just("@return")
-        .then(just(' ').repeated())
-        .ignore_then(rest_of_line.clone().or_not())
-        .then_ignore(just('\n').or_not())
-        .map(|description| Return {
-            description: description.filter(|s| !s.is_empty()),
-            return_type: None,
-            group: None,
-        });
-
-    let see = just("@see")
-        .then(just(' ').repeated().at_least(1))
-        .ignore_then(rest_of_line.clone())
-        .then_ignore(just('\n').or_not())
-        .map(|target| See {
-            reference: Ref {
-                target,
-                display: None,
-            },
-        });
-
-    #[derive(Debug)]
-    enum Tag {
-        Param(Param),
-        Return(Return),
-        See(See),
-    }
-
-    let tag = choice((
-        param.map(Tag::Param),
-        ret.map(Tag::Return),
-        see.map(Tag::See),
-    ));
-
-    // A full document comment block
-    // Layout:
-    //   <summary>
-    //   [<description lines>]
-    //   [@tag ...]*
-
-    summary
-        .then_ignore(just('\n'))
-        .then(description)
-        .then(tag.repeated().collect::<Vec<_>>())
-        .map(|((summary, description), tags)| {
-            let mut doc = DocComment {
-                summary,
-                description,
-                ..DocComment::default()
-            };
-
-            for tag in tags {
-                match tag {
-                    Tag::Param(p) => doc.params.push(p),
-                    Tag::Return(r) => doc.returns.push(r),
-                    Tag::See(s) => doc.see.push(s),
-                }
-            }
-
-            Item {
-                name: doc.name.clone().unwrap_or_default(),
-                doc,
-                kind: ItemKind::Function,
-                location: None,
-            }
-        })
-}
+use documentation::*;
+use plugin_doc::*;
 
-include!("./src/wrappers/toml.rs");
 fn main() {
     let scripts_dir = "src/plugins";
     println!("cargo:rerun-if-changed={}", scripts_dir);
 
     let mut stems = Vec::new();
-    let mut combined_parts = Vec::new();
+    let mut sources = Vec::new();
 
     for entry in fs::read_dir(scripts_dir)
         .unwrap()
@@ -154,14 +39,11 @@ fn main() {
         let path = entry.path();
         println!("cargo:rerun-if-changed={}", path.display());
         stems.push(path.file_stem().unwrap().to_string_lossy().into_owned());
-        combined_parts.push(fs::read_to_string(&path).unwrap());
+        let source = fs::read_to_string(&path).unwrap();
+        sources.push((path, source));
     }
 
-    let combined = combined_parts.join("\n");
     let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
-    let plugins_path = out_dir.join("all-plugins.scm");
-
-    fs::write(&plugins_path, &combined).unwrap();
 
     let mut engine = Engine::new();
     engine.with_contracts(true);
@@ -170,30 +52,31 @@ fn main() {
         include_str!("./src/core.scm").to_string(),
     );
 
-    engine.register_fn("parse-toml", parse_toml);
+    wrappers::toml::register(&mut engine);
+    // Docs are extracted without running anything, so host modules only need to exist.
+    engine.register_module(BuiltInModule::new("badger/fs"));
 
-    engine.run(combined.clone()).unwrap();
-
-    let ast = engine
-        .emit_expanded_ast_without_optimizations(&combined, Some(plugins_path))
-        .unwrap();
+    for (stem, (path, source)) in stems.iter().zip(&sources) {
+        if !provides(source, stem) {
+            panic!(
+                "{}: plugin must `(provide {stem})`, its entry point",
+                path.display()
+            );
+        }
+    }
 
     let plugins: Vec<PluginInfo> = stems
         .into_iter()
-        .map(|stem| {
-            let doc = query_top_level_define(&ast, &format!("{}__doc__", stem)).and_then(|node| {
-                let node = node.to_string();
-                Some(
-                    parser()
-                        .parse(extract_quoted_string(node.as_ref()))
-                        .into_result()
-                        .unwrap(),
-                )
-            });
+        .zip(sources)
+        .map(|(stem, (path, source))| {
+            let doc = extract_doc(&mut engine, &stem, &source, Some(path.clone()))
+                .unwrap_or_else(|e| panic!("{}: invalid doc comment: {e}", path.display()));
 
             PluginInfo {
                 entry_point: stem,
                 doc,
+                path,
+                source,
             }
         })
         .collect();
@@ -208,6 +91,21 @@ fn main() {
 struct PluginInfo {
     entry_point: String,
     doc: Option<Item>,
+    path: PathBuf,
+    source: String,
+}
+
+impl PluginInfo {
+    /// Abort the build with `message`, pointing at the `@param` line that declares `param`.
+    fn param_error(&self, param: &str, message: &str) -> ! {
+        let line = param_line(&self.source, param);
+
+        panic!(
+            "{}:{line}: parameter `{param}` of producer `{}`: {message}",
+            self.path.display(),
+            self.entry_point
+        );
+    }
 }
 
 fn to_variant_name(entry_point: &str) -> String {
@@ -223,59 +121,232 @@ fn to_variant_name(entry_point: &str) -> String {
         .collect()
 }
 
+/// The Rust type a vocabulary type name becomes in a generated field.
+fn vocabulary_type(name: &str) -> TokenStream {
+    let name = canonical_type(name).expect("type checked by `check_type`");
+    let rust = VOCABULARY
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|(_, rust)| *rust)
+        .unwrap();
+    rust.parse().unwrap()
+}
+
+/// Map a type expression, already accepted by [`check_type`], to the Rust type of a
+/// generated field. `{A=T, …}` records become named structs, pushed onto `records` and
+/// named after `prefix`.
+fn rust_type(ty: &TypeExpr, prefix: &str, records: &mut Vec<TokenStream>) -> TokenStream {
+    match ty {
+        TypeExpr::Named(n) => vocabulary_type(n),
+        TypeExpr::Nullable(t) => {
+            let t = rust_type(t, prefix, records);
+            quote! { Option<#t> }
+        }
+        TypeExpr::Union(types) => {
+            let rest: Vec<&TypeExpr> = types
+                .iter()
+                .filter(|t| !matches!(t, TypeExpr::Named(n) if n == "nil"))
+                .collect();
+            let inner = match rest.as_slice() {
+                [single] => rust_type(single, prefix, records),
+                _ => quote! { toml::Value },
+            };
+            if rest.len() == types.len() {
+                inner
+            } else {
+                quote! { Option<#inner> }
+            }
+        }
+        TypeExpr::Tuple(types) => {
+            let types = types.iter().map(|t| rust_type(t, prefix, records));
+            quote! { (#(#types),*) }
+        }
+        TypeExpr::Array(t) => {
+            let t = rust_type(t, prefix, records);
+            quote! { Vec<#t> }
+        }
+        TypeExpr::Map { value, .. } => map_type(value, prefix, records),
+        TypeExpr::Struct(fields) => {
+            let name = Ident::new(prefix, Span::call_site());
+            let names: Vec<Ident> = fields
+                .iter()
+                .map(|(n, _)| Ident::new_raw(n, Span::call_site()))
+                .collect();
+            let keys = fields.iter().map(|(n, _)| n.as_str());
+            let types: Vec<TokenStream> = fields
+                .iter()
+                .map(|(n, t)| rust_type(t, &format!("{prefix}{}", to_variant_name(n)), records))
+                .collect();
+
+            records.push(quote! {
+                #[derive(Debug, serde::Deserialize, Clone, PartialEq)]
+                pub struct #name {
+                    #(pub #names: #types),*
+                }
+                impl crate::params::IntoProducerArg for #name {
+                    fn into_producer_arg(self) -> Result<steel::SteelVal, steel::SteelErr> {
+                        let mut map = std::collections::HashMap::new();
+                        #(
+                            map.insert(
+                                #keys.to_string(),
+                                crate::params::IntoProducerArg::into_producer_arg(self.#names)?,
+                            );
+                        )*
+                        crate::params::IntoProducerArg::into_producer_arg(map)
+                    }
+                }
+            });
+
+            quote! { #name }
+        }
+        TypeExpr::Generic { name, args } => match (name.to_lowercase().as_str(), args.as_slice()) {
+            ("list" | "array", [t]) => {
+                let t = rust_type(t, prefix, records);
+                quote! { Vec<#t> }
+            }
+            ("option", [t]) => {
+                let t = rust_type(t, prefix, records);
+                quote! { Option<#t> }
+            }
+            ("map", [_, v]) => map_type(v, prefix, records),
+            _ => unreachable!("type checked by `check_type`"),
+        },
+    }
+}
+
+/// Whether a field of this type is `Copy`, and so passed by value rather than cloned.
+fn is_copy(ty: &TypeExpr) -> bool {
+    match ty {
+        TypeExpr::Named(n) => matches!(
+            canonical_type(n),
+            Some("int" | "float" | "bool" | "duration")
+        ),
+        TypeExpr::Nullable(t) => is_copy(t),
+        TypeExpr::Union(types) => {
+            let mut rest = types
+                .iter()
+                .filter(|t| !matches!(t, TypeExpr::Named(n) if n == "nil"));
+            match (rest.next(), rest.next()) {
+                (Some(single), None) => is_copy(single),
+                _ => false,
+            }
+        }
+        TypeExpr::Tuple(types) => types.iter().all(is_copy),
+        TypeExpr::Generic { name, args } => match (name.to_lowercase().as_str(), args.as_slice()) {
+            ("option", [t]) => is_copy(t),
+            _ => false,
+        },
+        TypeExpr::Array(_) | TypeExpr::Map { .. } | TypeExpr::Struct(_) => false,
+    }
+}
+
+/// Map keys are always `string`, so only the value type is lowered.
+fn map_type(value: &TypeExpr, prefix: &str, records: &mut Vec<TokenStream>) -> TokenStream {
+    let value = rust_type(value, prefix, records);
+    quote! { std::collections::HashMap<String, #value> }
+}
+
 fn generate_enum(plugins: &[PluginInfo]) -> TokenStream {
-    struct VariantInfo<'a> {
+    struct VariantInfo {
         name: Ident,
         fields: Option<proc_macro2::TokenStream>,
-        defaults: Option<proc_macro2::TokenStream>,
-        patterns: Option<proc_macro2::TokenStream>,
-        entry_point: &'a str,
+        args: Option<proc_macro2::TokenStream>,
     }
 
+    let mut default_fns = Vec::new();
+    let mut records = Vec::new();
+
     let infos: Vec<VariantInfo> = plugins
         .iter()
         .map(|item| {
             let name = Ident::new(&to_variant_name(&item.entry_point), Span::call_site());
 
-            let (fields, defaults, patterns) = match item
+            let (fields, args) = match item
                 .doc
                 .as_ref()
                 .map(|doc| &doc.doc.params)
                 .filter(|p| !p.is_empty())
             {
-                None => (None, None, None),
+                None => (None, None),
                 Some(params) => {
                     let fnames: Vec<Ident> = params
                         .iter()
                         .map(|p| Ident::new_raw(&p.name, Span::call_site()))
                         .collect();
 
-                    let ftypes: Vec<proc_macro2::TokenStream> = params
-                        .iter()
-                        .map(|p| match &p.param_type {
-                            Some(TypeExpr::Named(n)) => {
-                                let ident = Ident::new(n, Span::call_site());
-                                quote! { #ident }
+                    let mut fields = Vec::new();
+                    let mut values = Vec::new();
+
+                    for (param, fname) in params.iter().zip(&fnames) {
+                        let struct_prefix = format!(
+                            "{}{}",
+                            to_variant_name(&item.entry_point),
+                            to_variant_name(&param.name)
+                        );
+                        let ty = match &param.param_type {
+                            Some(t) => {
+                                check_type(t).unwrap_or_else(|e| item.param_error(&param.name, &e));
+                                rust_type(t, &struct_prefix, &mut records)
                             }
-                            _ => quote! { String },
-                        })
-                        .collect();
+                            None => quote! { String },
+                        };
+                        let nullable = param.param_type.as_ref().is_some_and(is_nullable);
+
+                        // Copy fields are passed by value; clippy rejects cloning them.
+                        values.push(if param.param_type.as_ref().is_some_and(is_copy) {
+                            quote! { *#fname }
+                        } else {
+                            quote! { #fname.clone() }
+                        });
+
+                        match Presence::of(param) {
+                            Presence::Required | Presence::Optional if nullable => {
+                                fields.push(quote! { #[serde(default)] #fname: #ty });
+                            }
+                            Presence::Required => {
+                                fields.push(quote! { #fname: #ty });
+                            }
+                            Presence::Optional => {
+                                fields.push(quote! { #[serde(default)] #fname: Option<#ty> });
+                            }
+                            Presence::Defaulted(value) => {
+                                check_default(param.param_type.as_ref(), value)
+                                    .unwrap_or_else(|e| item.param_error(&param.name, &e));
+
+                                let fn_name = Ident::new(
+                                    &format!(
+                                        "{}_{}",
+                                        item.entry_point.replace('-', "_"),
+                                        param.name
+                                    ),
+                                    Span::call_site(),
+                                );
+                                let path = format!("producer_defaults::{fn_name}");
+                                let message =
+                                    format!("invalid default {value:?} for `{}`", param.name);
+
+                                default_fns.push(quote! {
+                                    pub fn #fn_name() -> #ty {
+                                        #value.parse().expect(#message)
+                                    }
+                                });
+                                fields.push(quote! { #[serde(default = #path)] #fname: #ty });
+                            }
+                        }
+                    }
 
                     (
-                        Some(quote! { { #(#fnames: #ftypes),* } }),
-                        Some(quote! { { #(#fnames: Default::default()),* } }),
-                        Some(quote! { { .. } }),
+                        Some(quote! { { #(#fields),* } }),
+                        Some(quote! {
+                            { #(#fnames),* } => Ok(vec![
+                                #(crate::params::IntoProducerArg::into_producer_arg(#values)?),*
+                            ])
+                        }),
                     )
                 }
             };
 
-            VariantInfo {
-                name,
-                fields,
-                defaults,
-                patterns,
-                entry_point: item.entry_point.as_str(),
-            }
+            VariantInfo { name, fields, args }
         })
         .collect();
 
@@ -283,36 +354,51 @@ fn generate_enum(plugins: &[PluginInfo]) -> TokenStream {
         let (n, f) = (&i.name, &i.fields);
         quote! { #n #f }
     });
-    let entry_point_arms = infos.iter().map(|i| {
-        let (n, p, ep) = (&i.name, &i.patterns, i.entry_point);
-        quote! { Producer::#n #p => #ep, }
+    let args_arms = infos.iter().map(|i| {
+        let n = &i.name;
+        match &i.args {
+            Some(args) => quote! { Producer::#n #args, },
+            None => quote! { Producer::#n => Ok(vec![]), },
+        }
     });
-    let try_from_arms = infos.iter().map(|i| {
-        let (n, d, ep) = (&i.name, &i.defaults, i.entry_point);
-        quote! { #ep => Ok(Producer::#n #d), }
+    let builtins = plugins.iter().map(|item| {
+        let ep = item.entry_point.as_str();
+        let path = item.path.to_str().unwrap();
+        let absolute = fs::canonicalize(&item.path).unwrap();
+        let absolute = absolute.to_str().unwrap();
+        quote! {
+            BuiltinPlugin { name: #ep, path: #path, source: include_str!(#absolute) }
+        }
     });
 
     quote! {
-        #[derive(Debug, serde::Deserialize, Clone, PartialEq, Eq, Hash)]
+        #(#records)*
+        #[derive(Debug, serde::Deserialize, Clone, PartialEq)]
         #[serde(tag = "name", rename_all = "kebab-case")]
         pub enum Producer {
             #(#variants,)*
         }
         impl Producer {
-            pub fn entry_point(&self) -> &'static str {
+            /// The configured parameters, in declared order, as arguments to the entry point.
+            pub fn args(&self) -> Result<Vec<steel::SteelVal>, steel::SteelErr> {
                 match self {
-                    #(#entry_point_arms)*
+                    #(#args_arms)*
                 }
             }
         }
-        impl TryFrom<&str> for Producer {
-            type Error = String;
-            fn try_from(s: &str) -> Result<Self, Self::Error> {
-                match s {
-                    #(#try_from_arms)*
-                    _ => Err(format!("unknown producer: {}", s)),
-                }
-            }
+        /// A plugin compiled in from `src/plugins`.
+        pub struct BuiltinPlugin {
+            /// The entry point, named after the file.
+            pub name: &'static str,
+            /// Where the plugin lives in this repository, used as its Steel module name so
+            /// diagnostics point at the original file.
+            pub path: &'static str,
+            pub source: &'static str,
+        }
+        /// Every plugin under `src/plugins`.
+        pub const BUILTIN_PLUGINS: &[BuiltinPlugin] = &[#(#builtins),*];
+        mod producer_defaults {
+            #(#default_fns)*
         }
     }
 }
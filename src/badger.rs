@@ -1,6 +1,19 @@
-use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-use crate::Producer;
+use serde::{Deserialize, Deserializer};
+use tracing::{debug, info, warn};
+
+use crate::error::{BadgerError, BadgerResult};
+use crate::params::Duration;
+use crate::registry::ProducerConfig;
+use crate::svg::Style;
+
+/// Name of the standalone configuration file searched for during discovery.
+pub(crate) const CONFIG_FILE: &str = "badger.toml";
+
+/// Cargo manifests may carry the configuration under `[package.metadata.badger]`.
+const CARGO_MANIFEST: &str = "Cargo.toml";
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct Config {
@@ -13,12 +26,343 @@ pub(crate) struct Config {
 #[derive(Debug, Deserialize, Default)]
 pub(crate) struct Globals {
     pub(crate) scale: u32,
+    #[serde(default)]
+    pub(crate) targets: Targets,
+    #[serde(default)]
+    pub(crate) commands: Commands,
+}
+
+/// The external programs producers may start through `run-command`, under
+/// `[globals.commands]`. Nothing may run unless it is listed in `allow`.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct Commands {
+    /// Program names as plugins pass them, such as `cargo` or `node`. Entries are matched
+    /// exactly as written and never resolved through `PATH`, so `cargo` does not allow
+    /// `/usr/bin/cargo`, nor the other way round.
+    pub(crate) allow: Vec<String>,
+    /// Longest a single command may run before it is killed, at least a second. Plugins
+    /// may ask for less.
+    #[serde(deserialize_with = "command_timeout")]
+    pub(crate) timeout: Duration,
+    /// Bytes of stdout, and separately of stderr, kept from a single command.
+    pub(crate) max_output: usize,
+}
+
+impl Default for Commands {
+    fn default() -> Self {
+        Self {
+            allow: Vec::new(),
+            timeout: Duration::from_secs(10),
+            max_output: 64 * 1024,
+        }
+    }
+}
+
+/// A `timeout` of whole seconds, none of which would let any command finish.
+fn command_timeout<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    let timeout = Duration::deserialize(deserializer)?;
+    if timeout.to_std().is_zero() {
+        return Err(serde::de::Error::custom(
+            "timeout must be at least 1 second",
+        ));
+    }
+    Ok(timeout)
+}
+
+/// Markdown files to update: a single path, or a list of paths and glob patterns.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum Targets {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Default for Targets {
+    fn default() -> Self {
+        Self::One("README.md".to_string())
+    }
+}
+
+impl Targets {
+    fn patterns(&self) -> &[String] {
+        match self {
+            Self::One(pattern) => std::slice::from_ref(pattern),
+            Self::Many(patterns) => patterns,
+        }
+    }
+
+    /// Expand every pattern against `root`, in order and without duplicates. A pattern that
+    /// matches nothing is an error when `must_match` is set, as for an explicit `--target`,
+    /// and otherwise only a warning.
+    pub(crate) fn resolve(&self, root: &Path, must_match: bool) -> BadgerResult<Vec<PathBuf>> {
+        let root = root.to_str().ok_or_else(|| {
+            BadgerError::Config(format!(
+                "project root {} is not valid UTF-8",
+                root.display()
+            ))
+        })?;
+        // The checkout may live anywhere, so `[`, `*` or `?` in its path are not patterns.
+        let root = PathBuf::from(glob::Pattern::escape(root));
+        let mut files = Vec::new();
+
+        for pattern in self.patterns() {
+            let full = root.join(pattern);
+            let full = full.to_str().ok_or_else(|| {
+                BadgerError::Config(format!("target {pattern:?} is not valid UTF-8"))
+            })?;
+
+            let matches = glob::glob(full)
+                .map_err(|e| BadgerError::Config(format!("invalid target {pattern:?}: {e}")))?;
+
+            let mut matched = false;
+            for path in matches {
+                let path = path.map_err(|e| BadgerError::Io(e.into_error()))?;
+                if path.is_file() {
+                    matched = true;
+                    if !files.contains(&path) {
+                        files.push(path);
+                    }
+                }
+            }
+
+            if !matched {
+                if must_match {
+                    return Err(BadgerError::Config(format!(
+                        "target {pattern:?} matched no files"
+                    )));
+                }
+                warn!(pattern, "target matched no files");
+            }
+        }
+
+        Ok(files)
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct Badge {
+    /// Names the badge's SVG and cache record, so it may not hold a path separator.
+    #[serde(default, deserialize_with = "file_name_id")]
     pub(crate) id: Option<String>,
+    /// Placeholders with a matching `group="…"` attribute show this badge.
+    pub(crate) group: Option<String>,
     pub(crate) primary_color: String,
     pub(crate) secondary_color: String,
-    pub(crate) producer: Producer,
+    /// Image shown before the label, as a URL or `data:` URI.
+    pub(crate) icon: Option<String>,
+    /// Where the badge links to from the markdown.
+    pub(crate) link: Option<String>,
+    /// Tooltip of the SVG and of the markdown image.
+    pub(crate) title: Option<String>,
+    #[serde(default)]
+    pub(crate) style: Style,
+    pub(crate) producer: ProducerConfig,
+}
+
+/// A badge `id`, which becomes `<id>.svg` and `<id>.json` and so must stay a single file
+/// name inside the badges and cache directories.
+fn file_name_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    let id = Option::<String>::deserialize(deserializer)?;
+    if let Some(id) = &id
+        && (id.is_empty() || id == "." || id == ".." || id.contains(['/', '\\', '\0']))
+    {
+        return Err(serde::de::Error::custom(format!(
+            "badge id {id:?} must be a file name, without path separators"
+        )));
+    }
+    Ok(id)
+}
+
+/// A loaded configuration and the directory every relative path is resolved against.
+#[derive(Debug)]
+pub(crate) struct Project {
+    pub(crate) root: PathBuf,
+    pub(crate) config: Config,
+}
+
+impl Project {
+    /// Walk up from `start` until a directory holds a `badger.toml`, or a `Cargo.toml`
+    /// with a `[package.metadata.badger]` (or `[workspace.metadata.badger]`) table.
+    pub(crate) fn discover(start: &Path) -> BadgerResult<Self> {
+        let start = start.canonicalize()?;
+
+        for dir in start.ancestors() {
+            debug!(dir = %dir.display(), "looking for badger configuration");
+
+            let standalone = dir.join(CONFIG_FILE);
+            if standalone.is_file() {
+                return Self::load(&standalone, dir);
+            }
+
+            let manifest = dir.join(CARGO_MANIFEST);
+            if manifest.is_file()
+                && let Some(config) = Config::from_manifest(&manifest)?
+            {
+                info!(path = %manifest.display(), "loaded configuration from Cargo metadata");
+                return Ok(Self {
+                    root: dir.to_path_buf(),
+                    config,
+                });
+            }
+        }
+
+        Err(BadgerError::Config(format!(
+            "no {CONFIG_FILE} or [package.metadata.badger] found in {} or any parent directory",
+            start.display()
+        )))
+    }
+
+    /// Load an explicit configuration file, which may be a `badger.toml` or a `Cargo.toml`.
+    pub(crate) fn load(path: &Path, root: &Path) -> BadgerResult<Self> {
+        let config = if path.file_name().is_some_and(|name| name == CARGO_MANIFEST) {
+            Config::from_manifest(path)?.ok_or_else(|| {
+                BadgerError::Config(format!(
+                    "{} has no [package.metadata.badger] table",
+                    path.display()
+                ))
+            })?
+        } else {
+            toml::from_str(&read_config(path)?)?
+        };
+
+        info!(path = %path.display(), "loaded configuration");
+
+        Ok(Self {
+            root: root.canonicalize()?,
+            config,
+        })
+    }
+
+    /// Resolve a path from the command line or configuration against the project root.
+    pub(crate) fn resolve(&self, path: &Path) -> PathBuf {
+        self.root.join(path)
+    }
+}
+
+impl Config {
+    /// Read the `badger` metadata table out of a Cargo manifest, if it has one.
+    fn from_manifest(path: &Path) -> BadgerResult<Option<Self>> {
+        let mut manifest: toml::Table = toml::from_str(&read_config(path)?)?;
+
+        let metadata = ["package", "workspace"].into_iter().find_map(|section| {
+            manifest
+                .get_mut(section)?
+                .get_mut("metadata")?
+                .as_table_mut()?
+                .remove("badger")
+        });
+
+        metadata
+            .map(|table| table.try_into().map_err(BadgerError::from))
+            .transpose()
+    }
+}
+
+fn read_config(path: &Path) -> BadgerResult<String> {
+    fs::read_to_string(path)
+        .map_err(|e| BadgerError::Config(format!("could not read {}: {e}", path.display())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_targets_under_a_root_with_glob_characters() {
+        let root = std::env::temp_dir().join(format!("badger-[ci]*?-{}", std::process::id()));
+        fs::create_dir_all(root.join("docs")).unwrap();
+        for file in ["README.md", "docs/a.md", "docs/b.md"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        let targets = Targets::Many(vec![
+            "docs/*.md".into(),
+            "README.md".into(),
+            "docs/a.md".into(),
+        ]);
+        let resolved = targets.resolve(&root, true);
+        let missing = Targets::One("CHANGELOG.md".into());
+        let (strict, lenient) = (missing.resolve(&root, true), missing.resolve(&root, false));
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            resolved.unwrap(),
+            [
+                root.join("docs/a.md"),
+                root.join("docs/b.md"),
+                root.join("README.md")
+            ]
+        );
+        assert!(strict.is_err());
+        assert!(lenient.unwrap().is_empty());
+    }
+
+    #[test]
+    fn discovers_the_nearest_configuration_above_the_start() {
+        let root = std::env::temp_dir().join(format!("badger-discover-{}", std::process::id()));
+        let member = root.join("crates/member");
+        fs::create_dir_all(member.join("src")).unwrap();
+        let root = root.canonicalize().unwrap();
+        let member = member.canonicalize().unwrap();
+
+        let config = "badge = []\n[globals]\nscale = 2\n";
+        let package = "[package]\nname = \"member\"\n";
+        fs::write(root.join(CONFIG_FILE), config).unwrap();
+        fs::write(member.join(CARGO_MANIFEST), package).unwrap();
+        let above_plain_manifest = Project::discover(&member.join("src"));
+        let without_table = Project::load(&member.join(CARGO_MANIFEST), &member);
+
+        let metadata = format!("{package}[package.metadata.badger]\n{config}");
+        fs::write(member.join(CARGO_MANIFEST), metadata).unwrap();
+        let from_metadata = Project::discover(&member.join("src"));
+        fs::remove_dir_all(&root).unwrap();
+
+        let project = above_plain_manifest.unwrap();
+        assert_eq!(project.root, root);
+        assert_eq!(project.config.globals.scale, 2);
+        assert_eq!(from_metadata.unwrap().root, member);
+        let error = without_table.unwrap_err().to_string();
+        assert!(
+            error.contains("no [package.metadata.badger] table"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn rejects_badge_ids_that_are_not_file_names() {
+        let config = |id: &str| {
+            toml::from_str::<Config>(&format!(
+                "[globals]\nscale = 1\n[[badge]]\nid = {id:?}\nprimary_color = \"#555\"\n\
+                 secondary_color = \"#007ec6\"\nproducer = {{ name = \"crate-version\" }}"
+            ))
+        };
+
+        assert_eq!(
+            config("crate.version-2").unwrap().badges[0].id.as_deref(),
+            Some("crate.version-2")
+        );
+        for id in ["../escape", "nested/id", "c:\\escape", "..", ""] {
+            let error = config(id).unwrap_err().to_string();
+            assert!(error.contains("must be a file name"), "{id}: {error}");
+        }
+    }
+
+    #[test]
+    fn rejects_a_zero_command_timeout_on_load() {
+        let config = |timeout: &str| {
+            toml::from_str::<Config>(&format!(
+                "badge = []\n[globals]\nscale = 1\n[globals.commands]\ntimeout = {timeout}"
+            ))
+        };
+
+        assert_eq!(
+            config("\"2m\"").unwrap().globals.commands.timeout,
+            Duration::from_secs(120)
+        );
+        for zero in ["0", "\"0s\""] {
+            let error = config(zero).unwrap_err().to_string();
+            assert!(error.contains("at least 1 second"), "{error}");
+        }
+    }
 }
@@ -0,0 +1,366 @@
+//! What a producer looked at while it ran. Host functions read files, list directories and
+//! query git through [`Inputs`], which remembers enough about each to tell later whether it
+//! would still see the same thing.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::UNIX_EPOCH;
+
+use ignore::WalkBuilder;
+use serde::{Deserialize, Serialize};
+
+/// One thing a producer's result depends on, as it was when the producer ran.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub(crate) enum Input {
+    /// A file that was read, or that was looked for and missing.
+    File { path: PathBuf, state: FileState },
+    /// Whether something exists at `path`, for a producer that only asked that.
+    Exists { path: PathBuf, exists: bool },
+    /// The paths matching a glob pattern.
+    Glob { pattern: String, digest: String },
+    /// The files under a directory, as `.gitignore` filters them.
+    Walk { dir: PathBuf, digest: String },
+    /// The commits and refs of the repository containing `dir`.
+    Git { dir: PathBuf, digest: String },
+}
+
+/// A file's contents, with the size and modification time that let an unchanged file be
+/// recognised without reading it again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub(crate) enum FileState {
+    Missing,
+    Present {
+        len: u64,
+        /// Nanoseconds since the Unix epoch, where the filesystem keeps it.
+        modified: Option<u64>,
+        digest: String,
+    },
+}
+
+impl Input {
+    /// Whether the producer would see the same thing now.
+    pub(crate) fn is_current(&self) -> bool {
+        match self {
+            Self::File { path, state } => state.matches(path),
+            Self::Exists { path, exists } => path.exists() == *exists,
+            Self::Glob { pattern, digest } => {
+                glob_matches(pattern).is_ok_and(|matches| paths_digest(&matches) == *digest)
+            }
+            Self::Walk { dir, digest } => {
+                walk_files(dir).is_ok_and(|files| paths_digest(&files) == *digest)
+            }
+            Self::Git { dir, digest } => git_state(dir) == *digest,
+        }
+    }
+}
+
+impl FileState {
+    fn of(contents: &[u8], metadata: &fs::Metadata) -> Self {
+        Self::Present {
+            len: metadata.len(),
+            modified: modified(metadata),
+            digest: blake3::hash(contents).to_hex().to_string(),
+        }
+    }
+
+    /// Compare against the file on disk, reading it only when its size or modification
+    /// time moved.
+    fn matches(&self, path: &Path) -> bool {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return *self == Self::Missing,
+            Err(_) => return false,
+        };
+        let Self::Present {
+            len,
+            modified: then,
+            digest,
+        } = self
+        else {
+            return false;
+        };
+
+        if metadata.len() == *len && then.is_some() && modified(&metadata) == *then {
+            return true;
+        }
+        fs::read(path).is_ok_and(|contents| blake3::hash(&contents).to_hex().as_str() == digest)
+    }
+}
+
+fn modified(metadata: &fs::Metadata) -> Option<u64> {
+    let since_epoch = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    u64::try_from(since_epoch.as_nanos()).ok()
+}
+
+/// What the producer running now has looked at.
+#[derive(Debug, Default)]
+pub(crate) struct Recording {
+    pub(crate) inputs: Vec<Input>,
+    /// Set when the producer did something whose inputs cannot be known, such as running
+    /// a command; its result is never reused.
+    pub(crate) volatile: bool,
+}
+
+/// The recorder shared by every host function of an engine. Cloning it shares the
+/// recording.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Inputs(Arc<Mutex<Recording>>);
+
+impl Inputs {
+    fn recording(&self) -> MutexGuard<'_, Recording> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn record(&self, input: Input) {
+        let mut recording = self.recording();
+        if !recording.inputs.contains(&input) {
+            recording.inputs.push(input);
+        }
+    }
+
+    /// Hand over what was recorded since the last call, and start afresh.
+    pub(crate) fn take(&self) -> Recording {
+        std::mem::take(&mut *self.recording())
+    }
+
+    /// [`fs::read_to_string`], recording the file, or that it was missing.
+    pub(crate) fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        let read = fs::metadata(path).and_then(|metadata| Ok((metadata, fs::read(path)?)));
+        let (metadata, contents) = match read {
+            Ok(read) => read,
+            Err(e) => {
+                if e.kind() == io::ErrorKind::NotFound {
+                    self.record(Input::File {
+                        path: path.to_path_buf(),
+                        state: FileState::Missing,
+                    });
+                }
+                return Err(e);
+            }
+        };
+
+        self.record(Input::File {
+            path: path.to_path_buf(),
+            state: FileState::of(&contents, &metadata),
+        });
+        String::from_utf8(contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// [`Path::exists`], recording the answer.
+    pub(crate) fn exists(&self, path: &Path) -> bool {
+        let exists = path.exists();
+        self.record(Input::Exists {
+            path: path.to_path_buf(),
+            exists,
+        });
+        exists
+    }
+
+    /// Every path matching `pattern`, sorted, recording the matches.
+    pub(crate) fn glob(&self, pattern: &str) -> Result<Vec<PathBuf>, glob::PatternError> {
+        let matches = glob_matches(pattern)?;
+        self.record(Input::Glob {
+            pattern: pattern.to_string(),
+            digest: paths_digest(&matches),
+        });
+        Ok(matches)
+    }
+
+    /// Every file under `dir`, sorted, skipping hidden files and those ignored by
+    /// `.gitignore`. Recording the listing does not record the files themselves.
+    pub(crate) fn walk(&self, dir: &Path) -> Result<Vec<PathBuf>, ignore::Error> {
+        let files = walk_files(dir)?;
+        self.record(Input::Walk {
+            dir: dir.to_path_buf(),
+            digest: paths_digest(&files),
+        });
+        Ok(files)
+    }
+
+    /// Record the state of the repository containing `dir`, once per producer.
+    pub(crate) fn git(&self, dir: &Path) {
+        let recorded = self
+            .recording()
+            .inputs
+            .iter()
+            .any(|input| matches!(input, Input::Git { dir: seen, .. } if seen == dir));
+        if !recorded {
+            self.record(Input::Git {
+                dir: dir.to_path_buf(),
+                digest: git_state(dir),
+            });
+        }
+    }
+
+    /// Mark the running producer's result as one that must not be reused.
+    pub(crate) fn volatile(&self) {
+        self.recording().volatile = true;
+    }
+}
+
+fn glob_matches(pattern: &str) -> Result<Vec<PathBuf>, glob::PatternError> {
+    let mut matches: Vec<PathBuf> = glob::glob(pattern)?.flatten().collect();
+    matches.sort();
+    Ok(matches)
+}
+
+fn walk_files(dir: &Path) -> Result<Vec<PathBuf>, ignore::Error> {
+    let mut files = Vec::new();
+    // `.gitignore` applies even when the project is not a git checkout.
+    for entry in WalkBuilder::new(dir).require_git(false).build() {
+        let entry = entry?;
+        if entry.file_type().is_some_and(|t| t.is_file()) {
+            files.push(entry.into_path());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn paths_digest(paths: &[PathBuf]) -> String {
+    let mut hasher = blake3::Hasher::new();
+    for path in paths {
+        hasher.update(path.as_os_str().as_encoded_bytes());
+        hasher.update(b"\0");
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+/// `HEAD` and every ref, which between them decide what any history query returns. A
+/// directory outside a repository has a state too, so it is noticed if one appears.
+fn git_state(dir: &Path) -> String {
+    let mut hasher = blake3::Hasher::new();
+    match Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["show-ref", "--head"])
+        .output()
+    {
+        Ok(output) => {
+            hasher.update(&output.stdout);
+            hasher.update(&output.status.code().unwrap_or(-1).to_le_bytes());
+        }
+        Err(e) => {
+            hasher.update(e.to_string().as_bytes());
+        }
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory under the system temp dir, removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("badger-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn all_current(inputs: &[Input]) -> bool {
+        inputs.iter().all(Input::is_current)
+    }
+
+    #[test]
+    fn notices_a_changed_or_deleted_file() {
+        let dir = TempDir::new("inputs-file");
+        let path = dir.0.join("Cargo.toml");
+        fs::write(&path, "version = \"0.1.0\"").unwrap();
+
+        let inputs = Inputs::default();
+        assert_eq!(inputs.read_to_string(&path).unwrap(), "version = \"0.1.0\"");
+        let recording = inputs.take();
+        assert!(all_current(&recording.inputs));
+
+        fs::write(&path, "version = \"0.10.0\"").unwrap();
+        assert!(!all_current(&recording.inputs));
+
+        fs::remove_file(&path).unwrap();
+        assert!(!all_current(&recording.inputs));
+    }
+
+    #[test]
+    fn notices_a_missing_file_appearing() {
+        let dir = TempDir::new("inputs-missing");
+        let path = dir.0.join("lcov.info");
+
+        let inputs = Inputs::default();
+        assert!(inputs.read_to_string(&path).is_err());
+        assert!(!inputs.exists(&dir.0.join("package.json")));
+        let recording = inputs.take();
+        assert_eq!(recording.inputs.len(), 2);
+        assert!(all_current(&recording.inputs));
+
+        fs::write(&path, "LF:1\nLH:1\n").unwrap();
+        assert!(!all_current(&recording.inputs));
+    }
+
+    #[test]
+    fn notices_matches_and_listings_changing() {
+        let dir = TempDir::new("inputs-listing");
+        fs::create_dir_all(dir.0.join("src")).unwrap();
+        fs::write(dir.0.join("src/main.rs"), "").unwrap();
+        fs::write(dir.0.join(".gitignore"), "target\n").unwrap();
+
+        let inputs = Inputs::default();
+        let pattern = format!(
+            "{}/src/*.rs",
+            glob::Pattern::escape(dir.0.to_str().unwrap())
+        );
+        assert_eq!(inputs.glob(&pattern).unwrap().len(), 1);
+        assert_eq!(inputs.walk(&dir.0).unwrap(), [dir.0.join("src/main.rs")]);
+        let recording = inputs.take();
+        assert!(all_current(&recording.inputs));
+
+        // Ignored files are outside the listing, and do not invalidate it.
+        fs::create_dir_all(dir.0.join("target")).unwrap();
+        fs::write(dir.0.join("target/out.rs"), "").unwrap();
+        assert!(all_current(&recording.inputs));
+
+        fs::write(dir.0.join("src/lib.rs"), "").unwrap();
+        let [glob, walk] = recording.inputs.as_slice() else {
+            panic!("expected a glob and a walk, found {:?}", recording.inputs);
+        };
+        assert!(!glob.is_current());
+        assert!(!walk.is_current());
+    }
+
+    #[test]
+    fn records_each_input_once_until_taken() {
+        let dir = TempDir::new("inputs-once");
+        let path = dir.0.join("README.md");
+        fs::write(&path, "# Title").unwrap();
+
+        let inputs = Inputs::default();
+        inputs.read_to_string(&path).unwrap();
+        inputs.read_to_string(&path).unwrap();
+        inputs.volatile();
+
+        let recording = inputs.take();
+        assert_eq!(recording.inputs.len(), 1);
+        assert!(recording.volatile);
+
+        let next = inputs.take();
+        assert!(next.inputs.is_empty());
+        assert!(!next.volatile);
+    }
+}
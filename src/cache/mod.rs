@@ -0,0 +1,209 @@
+//! `.badger/cache`: what each badge's producer returned, what it read to get there, and
+//! what its SVG was drawn from. A producer whose inputs are unchanged is not run again, and
+//! an entry drawn in the same style as last time is not drawn again: the badge file already
+//! on disk is reused, as long as it is still the file that was written.
+
+pub(crate) mod inputs;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+pub(crate) use inputs::{Inputs, Recording};
+
+use crate::BUILTIN_PLUGINS;
+use crate::entry::Entry;
+use crate::error::BadgerResult;
+use crate::registry::{PLUGIN_DIR, ProducerConfig, local_plugins};
+use crate::steel_engine::CORE;
+use crate::svg::Style;
+use inputs::Input;
+
+/// Cache directory, relative to the project root.
+pub(crate) const CACHE_DIR: &str = ".badger/cache";
+
+/// What is remembered about one badge, stored as `<id>.json`.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Record {
+    /// Digest of the producer's code and parameters.
+    key: String,
+    /// What the producer read, or `None` when it did something untracked and must run again.
+    inputs: Option<Vec<Input>>,
+    /// The producer's result, before the badge's configuration filled it in.
+    entry: Entry,
+    /// Digest of what the badge file was drawn from.
+    render_key: String,
+    /// Digest of the badge file itself, so one edited by hand is drawn again.
+    svg: String,
+}
+
+/// The cache of a project, for the producers and plugins loaded by this build.
+#[derive(Debug)]
+pub(crate) struct Cache {
+    dir: PathBuf,
+    /// Digest of this build's core module and built-in plugins and of the project's local
+    /// plugins, any change to which may change what a producer returns.
+    code: String,
+    /// When unset, nothing is reused, but results are still stored for the next run.
+    reuse: bool,
+    /// When unset, nothing is written, for runs that must leave the project untouched.
+    store: bool,
+}
+
+impl Cache {
+    pub(crate) fn open(root: &Path, reuse: bool) -> BadgerResult<Self> {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+        hasher.update(CORE.as_bytes());
+        for plugin in BUILTIN_PLUGINS {
+            hasher.update(plugin.path.as_bytes());
+            hasher.update(plugin.source.as_bytes());
+        }
+        for path in local_plugins(&root.join(PLUGIN_DIR))? {
+            hasher.update(path.as_os_str().as_encoded_bytes());
+            hasher.update(&fs::read(&path)?);
+        }
+
+        Ok(Self {
+            dir: root.join(CACHE_DIR),
+            code: hasher.finalize().to_hex().to_string(),
+            reuse,
+            store: true,
+        })
+    }
+
+    /// The same cache, read but never written.
+    pub(crate) fn read_only(self) -> Self {
+        Self {
+            store: false,
+            ..self
+        }
+    }
+
+    /// Digest of everything besides its inputs that decides what `producer` returns.
+    pub(crate) fn producer_key(&self, producer: &ProducerConfig) -> String {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(self.code.as_bytes());
+        hasher.update(producer.name.as_bytes());
+        hasher.update(
+            toml::Value::Table(producer.params.clone())
+                .to_string()
+                .as_bytes(),
+        );
+        hasher.finalize().to_hex().to_string()
+    }
+
+    /// The badge's record from the last run, if there is one to reuse.
+    pub(crate) fn load(&self, id: &str) -> Option<Record> {
+        if !self.reuse {
+            return None;
+        }
+
+        let path = self.path(id);
+        let text = fs::read_to_string(&path).ok()?;
+        serde_json::from_str(&text)
+            .inspect_err(
+                |e| debug!(path = %path.display(), "ignoring unreadable cache record: {e}"),
+            )
+            .ok()
+    }
+
+    /// Remember this run's record for the next one. A cache that cannot be written only
+    /// costs time, so failures are logged rather than returned.
+    pub(crate) fn store(&self, id: &str, record: &Record) {
+        if !self.store {
+            return;
+        }
+
+        let written = fs::create_dir_all(&self.dir)
+            .and_then(|()| {
+                // Nothing under the cache belongs in version control.
+                let ignore = self.dir.join(".gitignore");
+                if ignore.exists() {
+                    Ok(())
+                } else {
+                    fs::write(ignore, "*\n")
+                }
+            })
+            .and_then(|()| Ok(serde_json::to_string(record)?))
+            .and_then(|json| fs::write(self.path(id), json));
+
+        if let Err(e) = written {
+            warn!(id, "cannot write cache record: {e}");
+        }
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.json"))
+    }
+}
+
+impl Record {
+    pub(crate) fn new(
+        key: String,
+        inputs: Option<Vec<Input>>,
+        entry: Entry,
+        render_key: String,
+        svg: &str,
+    ) -> Self {
+        Self {
+            key,
+            inputs,
+            entry,
+            render_key,
+            svg: blake3::hash(svg.as_bytes()).to_hex().to_string(),
+        }
+    }
+
+    /// The recorded entry, if it came from the same producer and parameters and everything
+    /// the producer read is as it was.
+    pub(crate) fn entry(&self, key: &str) -> Option<&Entry> {
+        let inputs = self.inputs.as_ref()?;
+        (self.key == key && inputs.iter().all(Input::is_current)).then_some(&self.entry)
+    }
+
+    /// What the producer read, unless it has to run again regardless.
+    pub(crate) fn into_inputs(self) -> Option<Vec<Input>> {
+        self.inputs
+    }
+
+    /// Whether `svg`, read back from the badge file, is what was last drawn from the same
+    /// entry in the same style.
+    pub(crate) fn drew(&self, render_key: &str, svg: &str) -> bool {
+        self.render_key == render_key && self.svg == blake3::hash(svg.as_bytes()).to_hex().as_str()
+    }
+}
+
+/// Digest of what an SVG is drawn from: the entry as configured, the style and the scale.
+pub(crate) fn render_key(entry: &Entry, style: Style, scale: u32) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+    hasher.update(
+        serde_json::to_string(&(entry, style, scale))
+            .unwrap_or_default()
+            .as_bytes(),
+    );
+    hasher.finalize().to_hex().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuses_only_the_badge_file_it_drew() {
+        let entry = Entry {
+            label: "version".to_string(),
+            message: "1.0.0".to_string(),
+            ..Entry::default()
+        };
+        let key = render_key(&entry, Style::default(), 1);
+        let record = Record::new(String::new(), None, entry.clone(), key.clone(), "<svg/>");
+
+        assert!(record.drew(&key, "<svg/>"));
+        assert!(!record.drew(&key, "<svg><!-- edited --></svg>"));
+        assert!(!record.drew(&render_key(&entry, Style::default(), 2), "<svg/>"));
+    }
+}
@@ -0,0 +1,83 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+
+/// Generate speckled SVG badges from Steel producers and splice them into markdown.
+#[derive(Debug, Parser)]
+#[command(name = "badger", version, about)]
+pub(crate) struct Cli {
+    #[command(flatten)]
+    pub(crate) paths: PathArgs,
+
+    /// Run every producer and draw every badge instead of reusing `.badger/cache`, which
+    /// is still refreshed for the next run.
+    #[arg(long, global = true)]
+    pub(crate) no_cache: bool,
+
+    #[command(subcommand)]
+    pub(crate) command: Command,
+}
+
+/// Locations shared by every subcommand. Relative paths resolve against the project root.
+#[derive(Debug, Args)]
+pub(crate) struct PathArgs {
+    /// Project root. Discovered by walking up from the working directory when omitted.
+    #[arg(long, global = true)]
+    pub(crate) root: Option<PathBuf>,
+
+    /// Badge configuration file, either a `badger.toml` or a `Cargo.toml` with
+    /// `[package.metadata.badger]`. Discovered alongside the root when omitted.
+    #[arg(long, global = true)]
+    pub(crate) config: Option<PathBuf>,
+
+    /// Directory the generated SVGs are written to.
+    #[arg(long, global = true, default_value = "badges")]
+    pub(crate) out_dir: PathBuf,
+
+    /// Markdown file containing the `<div badges="true">` placeholder. May be repeated,
+    /// and overrides `targets` from the configuration.
+    #[arg(long, global = true)]
+    pub(crate) target: Vec<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub(crate) enum Command {
+    /// Run every configured producer, write the SVGs and update the target.
+    Generate,
+
+    /// Run every producer and fail if the target or SVGs are out of date.
+    Check,
+
+    /// Print the built-in producers and the project's local plugins, with their parameters.
+    ListProducers,
+
+    /// Render a single badge from flags, without a configuration file.
+    Render(RenderArgs),
+
+    /// Remove the SVGs generated for the configured badges.
+    Clean,
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct RenderArgs {
+    /// Text on the left-hand side of the badge.
+    #[arg(long)]
+    pub(crate) label: Option<String>,
+
+    /// Text on the right-hand side of the badge.
+    #[arg(long)]
+    pub(crate) status: String,
+
+    #[arg(long, default_value = "blue")]
+    pub(crate) primary_color: String,
+
+    #[arg(long, default_value = "purple")]
+    pub(crate) secondary_color: String,
+
+    #[arg(long, default_value_t = 1)]
+    pub(crate) scale: u32,
+
+    /// Where to write the SVG. Prints to stdout when omitted.
+    #[arg(long, short)]
+    pub(crate) output: Option<PathBuf>,
+}
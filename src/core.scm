@@ -3,10 +3,42 @@
   (displayln "called with" (length args) "optional args")
   (displayln "optional args:" args))
 
+(require-builtin badger/fs)
+
 (provide file->string)
+;; The contents of a file of the project; see `read-file` in `badger/fs`.
 (define (file->string path)
-  (let ([file (open-input-file path)]) (read-port-to-string file)))
+  (read-file path))
 
 (provide make-entry)
+;; A producer's result: a hash of "label" and "message", plus any of "color",
+;; "label-color", "icon", "link", "title" and "style" to override the badge's configuration.
 (define (make-entry key value . icon)
-  (list key value (if (null? icon) #f (car icon))))
+  (if (null? icon)
+      (hash "label" key "message" value)
+      (hash "label" key "message" value "icon" (car icon))))
+
+(provide format-count)
+;; Abbreviate a count for a badge: 950, 12.3k, 4.5M.
+(define (format-count n)
+  (define (one-decimal x)
+    (trim-end-matches (number->string (/ (round (* x 10)) 10)) ".0"))
+  (cond [(>= n 1000000) (string-append (one-decimal (/ n 1000000.0)) "M")]
+        [(>= n 1000) (string-append (one-decimal (/ n 1000.0)) "k")]
+        [else (number->string n)]))
+
+(provide with-color)
+;; Give an entry its status color, a name such as "green" or a hex code like "#4c1".
+(define (with-color entry color)
+  (hash-insert entry "color" color))
+
+(provide command-output)
+;; The trimmed stdout of an allowed program run at the project root; a nonzero exit is an
+;; error carrying its stderr. See `run-command` for the directory and time limit.
+(define (command-output program . args)
+  (define result (run-command program args #f #f))
+  (if (equal? (hash-ref result "exit-code") 0)
+      (trim (hash-ref result "stdout"))
+      (error! (string-append program " exited with "
+                             (to-string (hash-ref result "exit-code")) ": "
+                             (trim (hash-ref result "stderr"))))))
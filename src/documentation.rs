@@ -244,7 +244,7 @@ pub enum ItemKind {
 // A complete doc-comment block
 
 /// The fully parsed representation of a single LDoc doc-comment.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct DocComment {
     /// One-sentence summary (ends with `.` or `?`).
     pub summary: String,
@@ -279,30 +279,6 @@ pub struct DocComment {
     pub module_tags: Vec<ModuleTag>,
 }
 
-impl Default for DocComment {
-    fn default() -> Self {
-        Self {
-            summary: String::new(),
-            description: None,
-            kind: None,
-            name: None,
-            location: None,
-            params: Vec::new(),
-            returns: Vec::new(),
-            errors: Vec::new(),
-            raises: Vec::new(),
-            fields: Vec::new(),
-            see: Vec::new(),
-            usage: Vec::new(),
-            is_local: false,
-            within: None,
-            section: None,
-            annotations: Vec::new(),
-            module_tags: Vec::new(),
-        }
-    }
-}
-
 // Project / module container
 
 /// A single documented item (function, table, field, …) inside a module.
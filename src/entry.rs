@@ -0,0 +1,319 @@
+//! What a producer returns: the text of a badge and, optionally, how to present it.
+
+use serde::{Deserialize, Serialize};
+use steel::SteelVal;
+use thiserror::Error;
+
+use crate::badger::Badge;
+use crate::error::{BadgerError, BadgerResult};
+
+/// The fields a producer may set, as named in the hash it returns.
+pub(crate) const FIELDS: &[&str] = &[
+    "label",
+    "message",
+    "color",
+    "label-color",
+    "icon",
+    "link",
+    "title",
+    "style",
+];
+
+/// The fields of the positional list form, in order.
+const LIST_FIELDS: &[&str] = &["label", "message", "icon", "color"];
+
+/// Longest rendering of a returned value quoted in an error.
+const MAX_QUOTED: usize = 120;
+
+/// Why a producer's return value is not an entry.
+#[derive(Debug, Error)]
+pub enum EntryError {
+    #[error("expected a hash of entry fields or a list, got a {0}")]
+    Shape(&'static str),
+
+    #[error("a list entry needs a label and a message, got {0} item(s)")]
+    TooShort(usize),
+
+    #[error("a list entry has at most 4 items (label, message, icon, color), got {0}")]
+    TooLong(usize),
+
+    #[error("missing field `{0}`")]
+    Missing(&'static str),
+
+    #[error("unknown field `{0}`; expected one of {fields}", fields = FIELDS.join(", "))]
+    UnknownField(String),
+
+    #[error("field names must be strings or symbols, got a {0}")]
+    Key(&'static str),
+
+    #[error("field `{field}` must be {expected}, got a {found}")]
+    Type {
+        field: String,
+        expected: &'static str,
+        found: &'static str,
+    },
+}
+
+/// A producer's result. Anything the producer sets overrides the badge's configuration
+/// for this run, so a failing check can turn its own badge red.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct Entry {
+    pub(crate) label: String,
+    pub(crate) message: String,
+    /// Status background, replacing the badge's `secondary_color`.
+    pub(crate) color: Option<String>,
+    /// Label background, replacing the badge's `primary_color`.
+    pub(crate) label_color: Option<String>,
+    /// Image shown before the label, as a URL or `data:` URI.
+    pub(crate) icon: Option<String>,
+    /// Where the badge links to from the markdown.
+    pub(crate) link: Option<String>,
+    /// Tooltip of the SVG and of the markdown image.
+    pub(crate) title: Option<String>,
+    pub(crate) style: Option<String>,
+}
+
+impl Entry {
+    /// Convert what `producer` returned, naming the producer and the value on failure.
+    pub(crate) fn from_producer(producer: &str, value: SteelVal) -> BadgerResult<Self> {
+        Self::try_from(&value).map_err(|source| BadgerError::Entry {
+            producer: producer.to_string(),
+            returned: quote(&value),
+            source,
+        })
+    }
+
+    /// Fill in whatever the producer left unset from the badge's configuration.
+    pub(crate) fn or_configured(mut self, badge: &Badge) -> Self {
+        self.color
+            .get_or_insert_with(|| badge.secondary_color.clone());
+        self.label_color
+            .get_or_insert_with(|| badge.primary_color.clone());
+        self.icon = self.icon.or_else(|| badge.icon.clone());
+        self.link = self.link.or_else(|| badge.link.clone());
+        self.title = self.title.or_else(|| badge.title.clone());
+        self
+    }
+
+    fn set(&mut self, field: &str, value: &SteelVal) -> Result<(), EntryError> {
+        let slot = match field {
+            "label" => {
+                self.label = text(field, value)?;
+                return Ok(());
+            }
+            "message" => {
+                self.message = text(field, value)?;
+                return Ok(());
+            }
+            "color" => &mut self.color,
+            "label-color" => &mut self.label_color,
+            "icon" => &mut self.icon,
+            "link" => &mut self.link,
+            "title" => &mut self.title,
+            "style" => &mut self.style,
+            other => return Err(EntryError::UnknownField(other.to_string())),
+        };
+
+        *slot = match value {
+            SteelVal::BoolV(false) => None,
+            SteelVal::StringV(s) => Some(s.to_string()),
+            other => {
+                return Err(EntryError::Type {
+                    field: field.to_string(),
+                    expected: "a string or #f",
+                    found: type_of(other),
+                });
+            }
+        };
+        Ok(())
+    }
+}
+
+impl TryFrom<&SteelVal> for Entry {
+    type Error = EntryError;
+
+    /// A hash of the fields in [`FIELDS`], keyed by string or symbol, with at least a
+    /// `label` and a `message`. The positional `(label message icon color)` list older
+    /// plugins build is still accepted.
+    fn try_from(value: &SteelVal) -> Result<Self, Self::Error> {
+        let mut entry = Entry::default();
+
+        match value {
+            SteelVal::HashMapV(map) => {
+                let mut seen = Vec::new();
+                for (key, value) in map.iter() {
+                    let field = match key {
+                        SteelVal::StringV(s) => s.to_string(),
+                        SteelVal::SymbolV(s) => s.to_string(),
+                        other => return Err(EntryError::Key(type_of(other))),
+                    };
+                    entry.set(&field, value)?;
+                    seen.push(field);
+                }
+
+                for required in ["label", "message"] {
+                    if !seen.iter().any(|field| field == required) {
+                        return Err(EntryError::Missing(required));
+                    }
+                }
+            }
+            SteelVal::ListV(list) => {
+                match list.len() {
+                    n if n < 2 => return Err(EntryError::TooShort(n)),
+                    n if n > LIST_FIELDS.len() => return Err(EntryError::TooLong(n)),
+                    _ => {}
+                }
+                for (field, value) in LIST_FIELDS.iter().zip(list.iter()) {
+                    entry.set(field, value)?;
+                }
+            }
+            other => return Err(EntryError::Shape(type_of(other))),
+        }
+
+        Ok(entry)
+    }
+}
+
+/// Badge text from a string, number or boolean, without the quoting of Steel's printer.
+fn text(field: &str, value: &SteelVal) -> Result<String, EntryError> {
+    match value {
+        SteelVal::StringV(s) => Ok(s.to_string()),
+        SteelVal::IntV(n) => Ok(n.to_string()),
+        SteelVal::NumV(n) => Ok(n.to_string()),
+        SteelVal::BoolV(b) => Ok(b.to_string()),
+        other => Err(EntryError::Type {
+            field: field.to_string(),
+            expected: "a string, number or boolean",
+            found: type_of(other),
+        }),
+    }
+}
+
+fn type_of(value: &SteelVal) -> &'static str {
+    match value {
+        SteelVal::StringV(_) => "string",
+        SteelVal::IntV(_) | SteelVal::BigNum(_) => "integer",
+        SteelVal::NumV(_) => "float",
+        SteelVal::BoolV(_) => "boolean",
+        SteelVal::CharV(_) => "character",
+        SteelVal::SymbolV(_) => "symbol",
+        SteelVal::ListV(_) => "list",
+        SteelVal::VectorV(_) | SteelVal::MutableVector(_) => "vector",
+        SteelVal::HashMapV(_) => "hash",
+        SteelVal::Void => "void",
+        SteelVal::Closure(_) | SteelVal::FuncV(_) | SteelVal::BoxedFunction(_) => "procedure",
+        _ => "value",
+    }
+}
+
+/// The value as Steel prints it, on one line and shortened for an error message.
+fn quote(value: &SteelVal) -> String {
+    let printed = value
+        .to_string()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    match printed.char_indices().nth(MAX_QUOTED) {
+        Some((end, _)) => format!("{}…", &printed[..end]),
+        None => printed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use steel::steel_vm::engine::Engine;
+
+    use super::*;
+
+    fn value(expr: &str) -> SteelVal {
+        Engine::new_sandboxed()
+            .run(expr.to_string())
+            .unwrap()
+            .pop()
+            .unwrap()
+    }
+
+    fn entry(expr: &str) -> Result<Entry, EntryError> {
+        Entry::try_from(&value(expr))
+    }
+
+    #[test]
+    fn converts_hashes_and_lists() {
+        let hashed =
+            entry(r#"(hash "label" "tests" 'message 12 "color" #f "title" "Tests")"#).unwrap();
+        assert_eq!(
+            (hashed.label.as_str(), hashed.message.as_str()),
+            ("tests", "12")
+        );
+        assert_eq!(hashed.color, None);
+        assert_eq!(hashed.title.as_deref(), Some("Tests"));
+
+        let listed = entry(r#"(list "edition" 2024 #f "green")"#).unwrap();
+        assert_eq!(
+            (listed.label.as_str(), listed.message.as_str()),
+            ("edition", "2024")
+        );
+        assert_eq!(
+            (listed.icon, listed.color.as_deref()),
+            (None, Some("green"))
+        );
+    }
+
+    #[test]
+    fn rejects_values_that_are_not_entries() {
+        assert!(matches!(
+            entry(r#""2024""#),
+            Err(EntryError::Shape("string"))
+        ));
+        assert!(matches!(entry("42"), Err(EntryError::Shape("integer"))));
+        assert!(matches!(
+            entry(r#"(list "edition")"#),
+            Err(EntryError::TooShort(1))
+        ));
+        assert!(matches!(
+            entry(r#"(list "a" "b" "c" "d" "e")"#),
+            Err(EntryError::TooLong(5))
+        ));
+    }
+
+    #[test]
+    fn rejects_missing_and_unknown_fields() {
+        assert!(matches!(
+            entry(r#"(hash "label" "edition")"#),
+            Err(EntryError::Missing("message"))
+        ));
+        assert!(matches!(
+            entry(r#"(hash 'message "2024")"#),
+            Err(EntryError::Missing("label"))
+        ));
+        assert!(matches!(
+            entry(r#"(hash "label" "a" "message" "b" "colour" "red")"#),
+            Err(EntryError::UnknownField(field)) if field == "colour"
+        ));
+        assert!(matches!(
+            entry(r#"(hash "label" "a" "message" "b" 1 "red")"#),
+            Err(EntryError::Key("integer"))
+        ));
+    }
+
+    #[test]
+    fn rejects_wrongly_typed_fields() {
+        assert!(matches!(
+            entry(r#"(hash "label" "a" "message" (list 1 2))"#),
+            Err(EntryError::Type { field, found: "list", .. }) if field == "message"
+        ));
+        assert!(matches!(
+            entry(r#"(hash "label" "a" "message" "b" "color" 3)"#),
+            Err(EntryError::Type { field, expected: "a string or #f", found: "integer" })
+                if field == "color"
+        ));
+
+        let error = Entry::from_producer("get-edition", value(r#"(list "edition")"#))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains(r#"`get-edition` returned ("edition"): a list entry"#),
+            "{error}"
+        );
+    }
+}
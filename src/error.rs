@@ -19,6 +19,19 @@ pub enum BadgerError {
 
     #[error("Config error: {0}")]
     Config(String),
+
+    #[error("Plugin error: {0}")]
+    Plugin(String),
+
+    #[error("Producer `{producer}` returned {returned}: {source}")]
+    Entry {
+        producer: String,
+        returned: String,
+        source: crate::entry::EntryError,
+    },
+
+    #[error("Badges are out of date: {0}")]
+    OutOfDate(String),
 }
 
 pub type BadgerResult<T> = std::result::Result<T, BadgerError>;
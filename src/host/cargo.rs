@@ -0,0 +1,471 @@
+//! `cargo-package`: a crate's `[package]` table, as Cargo itself would resolve it, and the
+//! workspace around it.
+
+use std::path::{Path, PathBuf};
+
+use steel::SteelErr;
+use steel::SteelVal;
+use steel::rerrs::ErrorKind;
+use steel::steel_vm::{engine::Engine, register_fn::RegisterFn};
+
+use crate::cache::Inputs;
+use crate::wrappers::toml::toml_value_to_steelval;
+
+const MANIFEST: &str = "Cargo.toml";
+const LOCKFILE: &str = "Cargo.lock";
+
+/// Register the Cargo host functions against the project at `root`, recording the
+/// manifests and lockfile they read in `inputs`:
+///
+/// - `(cargo-package name)`: the `[package]` table of the crate at `root`, or of the
+///   workspace member called `name`, with inherited fields filled in.
+/// - `(cargo-workspace-root)`: the directory holding the workspace's `Cargo.toml` and
+///   `Cargo.lock`; the crate's own directory outside a workspace.
+/// - `(cargo-workspace-members)`: the package names of every workspace member.
+/// - `(cargo-lock)`: the workspace's parsed `Cargo.lock`, which may live above the project
+///   root and so out of reach of `badger/fs`.
+pub(crate) fn register(engine: &mut Engine, root: &Path, inputs: &Inputs) {
+    let package_root = root.to_path_buf();
+    let package_inputs = inputs.clone();
+    engine.register_fn(
+        "cargo-package",
+        move |name: Option<String>| -> Result<SteelVal, SteelErr> {
+            let package =
+                package(&package_root, name.as_deref(), &package_inputs).map_err(generic)?;
+
+            toml_value_to_steelval(toml::Value::Table(package))
+                .map_err(|e| SteelErr::new(ErrorKind::ConversionError, e))
+        },
+    );
+
+    let workspace_root = root.to_path_buf();
+    let workspace_inputs = inputs.clone();
+    engine.register_fn(
+        "cargo-workspace-root",
+        move || -> Result<String, SteelErr> {
+            let manifest = Manifest::read(&workspace_root, &workspace_inputs).map_err(generic)?;
+            let workspace = manifest.workspace(&workspace_inputs).unwrap_or(manifest);
+
+            Ok(workspace.dir().to_string_lossy().into_owned())
+        },
+    );
+
+    let members_root = root.to_path_buf();
+    let members_inputs = inputs.clone();
+    engine.register_fn(
+        "cargo-workspace-members",
+        move || -> Result<Vec<String>, SteelErr> {
+            let manifest = Manifest::read(&members_root, &members_inputs).map_err(generic)?;
+            let members = match manifest.workspace(&members_inputs) {
+                Some(workspace) => workspace.members(&members_inputs),
+                None => vec![manifest],
+            };
+
+            Ok(members
+                .iter()
+                .filter_map(|m| m.package_name().map(str::to_string))
+                .collect())
+        },
+    );
+
+    let lock_root = root.to_path_buf();
+    let lock_inputs = inputs.clone();
+    engine.register_fn("cargo-lock", move || -> Result<SteelVal, SteelErr> {
+        let manifest = Manifest::read(&lock_root, &lock_inputs).map_err(generic)?;
+        let workspace = manifest.workspace(&lock_inputs).unwrap_or(manifest);
+        let path = workspace.dir().join(LOCKFILE);
+
+        let text = lock_inputs.read_to_string(&path).map_err(|e| {
+            SteelErr::new(
+                ErrorKind::Io,
+                format!("cannot read {}: {e}", path.display()),
+            )
+        })?;
+        let lock: toml::Value =
+            toml::from_str(&text).map_err(|e| generic(format!("{}: {e}", path.display())))?;
+
+        toml_value_to_steelval(lock).map_err(|e| SteelErr::new(ErrorKind::ConversionError, e))
+    });
+}
+
+fn generic(message: String) -> SteelErr {
+    SteelErr::new(ErrorKind::Generic, message)
+}
+
+/// A parsed `Cargo.toml`.
+#[derive(Clone)]
+struct Manifest {
+    path: PathBuf,
+    table: toml::Table,
+}
+
+impl Manifest {
+    fn read(dir: &Path, inputs: &Inputs) -> Result<Self, String> {
+        let path = dir.join(MANIFEST);
+        let text = inputs
+            .read_to_string(&path)
+            .map_err(|e| format!("cannot read {}: {e}", path.display()))?;
+        let table = toml::from_str(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+
+        Ok(Self { path, table })
+    }
+
+    fn dir(&self) -> &Path {
+        self.path.parent().unwrap_or(Path::new("."))
+    }
+
+    fn section(&self, name: &str) -> Option<&toml::Table> {
+        self.table.get(name).and_then(toml::Value::as_table)
+    }
+
+    fn package_name(&self) -> Option<&str> {
+        self.section("package")?.get("name")?.as_str()
+    }
+
+    /// Where this manifest's workspace root lives: here, wherever `package.workspace`
+    /// points, or the nearest ancestor with a `[workspace]` table.
+    fn workspace(&self, inputs: &Inputs) -> Option<Manifest> {
+        if self.section("workspace").is_some() {
+            return Some(self.clone());
+        }
+
+        if let Some(explicit) = self
+            .section("package")
+            .and_then(|p| p.get("workspace"))
+            .and_then(toml::Value::as_str)
+        {
+            return Manifest::read(&self.dir().join(explicit), inputs).ok();
+        }
+
+        self.dir()
+            .ancestors()
+            .skip(1)
+            .filter_map(|dir| Manifest::read(dir, inputs).ok())
+            .find(|m| m.section("workspace").is_some())
+    }
+
+    /// Every member manifest of this workspace root, honouring `members` globs and `exclude`.
+    fn members(&self, inputs: &Inputs) -> Vec<Manifest> {
+        let Some(workspace) = self.section("workspace") else {
+            return Vec::new();
+        };
+        let list = |key: &str| -> Vec<&str> {
+            workspace
+                .get(key)
+                .and_then(toml::Value::as_array)
+                .map(|a| a.iter().filter_map(toml::Value::as_str).collect())
+                .unwrap_or_default()
+        };
+        let excluded: Vec<PathBuf> = list("exclude").iter().map(|e| self.dir().join(e)).collect();
+
+        let mut members = Vec::new();
+        if self.section("package").is_some() {
+            members.push(Manifest::read(self.dir(), inputs));
+        }
+
+        for pattern in list("members") {
+            let pattern = self.dir().join(pattern);
+            let Ok(dirs) = inputs.glob(&pattern.to_string_lossy()) else {
+                continue;
+            };
+            for dir in dirs {
+                if inputs.exists(&dir.join(MANIFEST)) && !excluded.contains(&dir) {
+                    members.push(Manifest::read(&dir, inputs));
+                }
+            }
+        }
+
+        members.into_iter().flatten().collect()
+    }
+}
+
+/// The `[package]` table of the crate at `root` when `name` is `None`, otherwise of the
+/// workspace member `name`, with `{ workspace = true }` fields resolved.
+pub(crate) fn package(
+    root: &Path,
+    name: Option<&str>,
+    inputs: &Inputs,
+) -> Result<toml::Table, String> {
+    let manifest = Manifest::read(root, inputs)?;
+    let workspace = manifest.workspace(inputs);
+
+    let selected = match name {
+        None if manifest.section("package").is_some() => manifest,
+        Some(name) if manifest.package_name() == Some(name) => manifest,
+        _ => {
+            let members = workspace
+                .as_ref()
+                .map(|w| w.members(inputs))
+                .unwrap_or_default();
+            select_member(members, name, root)?
+        }
+    };
+
+    let mut table = selected
+        .section("package")
+        .cloned()
+        .ok_or_else(|| format!("{} has no [package] table", selected.path.display()))?;
+
+    let inherited = workspace
+        .as_ref()
+        .and_then(|w| w.section("workspace"))
+        .and_then(|w| w.get("package"))
+        .and_then(toml::Value::as_table);
+
+    for (key, value) in table.iter_mut() {
+        if !is_inherited(value) {
+            continue;
+        }
+        *value = inherited.and_then(|p| p.get(key)).cloned().ok_or_else(|| {
+            format!(
+                "{}: `{key}.workspace = true`, but [workspace.package] has no `{key}`",
+                selected.path.display()
+            )
+        })?;
+    }
+
+    Ok(table)
+}
+
+fn select_member(
+    members: Vec<Manifest>,
+    name: Option<&str>,
+    root: &Path,
+) -> Result<Manifest, String> {
+    let names: Vec<String> = members
+        .iter()
+        .filter_map(|m| m.package_name().map(str::to_string))
+        .collect();
+
+    match name {
+        Some(name) => members
+            .into_iter()
+            .find(|m| m.package_name() == Some(name))
+            .ok_or_else(|| {
+                format!(
+                    "no workspace member named `{name}`; members are: {}",
+                    names.join(", ")
+                )
+            }),
+        None if members.len() == 1 => Ok(members.into_iter().next().unwrap()),
+        None => Err(format!(
+            "{} is a workspace with members {}; pick one with the `package` parameter",
+            root.join(MANIFEST).display(),
+            names.join(", ")
+        )),
+    }
+}
+
+/// `field.workspace = true`, or the `field = { workspace = true }` spelling of it.
+fn is_inherited(value: &toml::Value) -> bool {
+    value
+        .as_table()
+        .and_then(|t| t.get("workspace"))
+        .and_then(toml::Value::as_bool)
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::badger::Commands;
+    use crate::entry::Entry;
+    use crate::steel_engine::setup;
+
+    /// A workspace with a virtual root, members under `crates/` and one excluded crate.
+    fn workspace(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("badger-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for (dir, manifest) in [
+            (
+                "",
+                "[workspace]\nmembers = [\"crates/*\"]\nexclude = [\"crates/skipped\"]\n\
+                 [workspace.package]\nversion = \"1.2.3\"\nedition = \"2024\"\n",
+            ),
+            (
+                "crates/app",
+                "[package]\nname = \"app\"\nversion.workspace = true\n\
+                 edition = { workspace = true }\nlicense = \"MIT\"\n",
+            ),
+            (
+                "crates/lib",
+                "[package]\nname = \"lib\"\nversion = \"0.1.0\"\nrust-version.workspace = true\n",
+            ),
+            ("crates/skipped", "[package]\nname = \"skipped\"\n"),
+        ] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            std::fs::write(root.join(dir).join(MANIFEST), manifest).unwrap();
+        }
+        root
+    }
+
+    fn field<'a>(package: &'a toml::Table, key: &str) -> Option<&'a str> {
+        package.get(key).and_then(toml::Value::as_str)
+    }
+
+    #[test]
+    fn inherits_workspace_fields() {
+        let root = workspace("cargo-inherit");
+        let inputs = Inputs::default();
+
+        let by_name = package(&root, Some("app"), &inputs);
+        let from_member = package(&root.join("crates/app"), None, &inputs);
+        let missing = package(&root, Some("lib"), &inputs);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        for app in [by_name.unwrap(), from_member.unwrap()] {
+            assert_eq!(field(&app, "name"), Some("app"));
+            assert_eq!(field(&app, "version"), Some("1.2.3"));
+            assert_eq!(field(&app, "edition"), Some("2024"));
+            assert_eq!(field(&app, "license"), Some("MIT"));
+        }
+        let error = missing.unwrap_err();
+        assert!(
+            error.contains("[workspace.package] has no `rust-version`"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn selects_members_by_the_package_param() {
+        let root = workspace("cargo-members");
+        let inputs = Inputs::default();
+
+        let lib = package(&root, Some("lib"), &inputs);
+        let unnamed = package(&root, None, &inputs);
+        let excluded = package(&root, Some("skipped"), &inputs);
+        std::fs::write(
+            root.join(MANIFEST),
+            "[package]\nname = \"root\"\nversion = \"2.0.0\"\n[workspace]\nmembers = [\"crates/app\"]\n",
+        )
+        .unwrap();
+        let root_package = package(&root, None, &inputs);
+        let member = package(&root, Some("app"), &inputs);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(lib.unwrap_err().contains("rust-version"));
+        let error = unnamed.unwrap_err();
+        assert!(
+            error.contains("pick one with the `package` parameter"),
+            "{error}"
+        );
+        assert!(error.contains("app, lib"), "{error}");
+        let error = excluded.unwrap_err();
+        assert!(
+            error.contains("no workspace member named `skipped`"),
+            "{error}"
+        );
+        assert_eq!(field(&root_package.unwrap(), "version"), Some("2.0.0"));
+        // The root's `[workspace]` no longer has a `[workspace.package]` to inherit from.
+        let error = member.unwrap_err();
+        assert!(error.contains("but [workspace.package] has no"), "{error}");
+    }
+
+    /// The message of the built-in producer `call` in the project at `root`.
+    fn produce(root: &Path, call: &str) -> Result<String, String> {
+        let mut engine = setup(root, &Commands::default(), &Inputs::default()).unwrap();
+        let value = engine.run(call.to_string()).map_err(|e| e.to_string())?;
+        let entry = Entry::from_producer(call, value.last().unwrap().clone()).unwrap();
+        Ok(entry.message)
+    }
+
+    #[test]
+    fn shows_package_metadata() {
+        let root = workspace("cargo-producers");
+        std::fs::write(
+            root.join("crates/lib/Cargo.toml"),
+            "[package]\nname = \"lib\"\nversion = \"0.4.0\"\nrust-version = \"1.85\"\n\
+             license = \"mit/Apache-2.0 WITH llvm-exception\"\n\
+             categories = [\"parsing\", \"encoding\", \"no-std\", \"wasm\"]\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("crates/skipped/Cargo.toml"),
+            "[package]\nname = \"skipped\"\nlicense-file = \"LICENSE\"\n",
+        )
+        .unwrap();
+
+        let app = root.join("crates/app");
+        let results = [
+            produce(&root, r#"(crate-version "app" "v")"#),
+            produce(&root, r#"(crate-version "lib" "")"#),
+            produce(&root, r#"(crate-license "lib")"#),
+            produce(&root.join("crates/skipped"), "(crate-license #f)"),
+            produce(&root, r#"(rust-version "lib" "MSRV")"#),
+            produce(&root, r#"(crate-categories "lib" 3)"#),
+            produce(&app, "(crate-categories #f 3)"),
+        ];
+        let no_msrv = produce(&app, r#"(rust-version #f "MSRV")"#);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            results.map(Result::unwrap),
+            [
+                "v1.2.3",
+                "0.4.0",
+                "MIT OR Apache-2.0 WITH LLVM-exception",
+                "custom",
+                "1.85+",
+                "parsing, encoding, no-std",
+                "none",
+            ]
+        );
+        let error = no_msrv.unwrap_err();
+        assert!(error.contains("does not declare a rust-version"), "{error}");
+    }
+
+    #[test]
+    fn summarises_the_lockfile() {
+        let root = workspace("cargo-lock");
+        std::fs::write(
+            root.join(LOCKFILE),
+            r#"version = 4
+
+[[package]]
+name = "app"
+version = "1.2.3"
+dependencies = ["lib", "local", "rand", "serde 1.0.0"]
+
+[[package]]
+name = "lib"
+version = "0.1.0"
+dependencies = ["serde 0.9.0"]
+
+[[package]]
+name = "local"
+version = "0.1.0"
+
+[[package]]
+name = "rand"
+version = "0.9.0"
+source = "git+https://example.com/rand#abc"
+
+[[package]]
+name = "serde"
+version = "0.9.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "serde"
+version = "1.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#,
+        )
+        .unwrap();
+
+        let metrics = ["summary", "total", "direct", "duplicates", "git", "path"].map(|metric| {
+            produce(
+                &root.join("crates/app"),
+                &format!("(dependencies {metric:?} \"deps\")"),
+            )
+        });
+        let unknown = produce(&root, r#"(dependencies "size" "deps")"#);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            metrics.map(Result::unwrap),
+            ["4 (1 dup, 1 git, 1 path)", "4", "3", "1", "1", "1"]
+        );
+        assert!(unknown.unwrap_err().contains("unknown metric"));
+    }
+}
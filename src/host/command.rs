@@ -0,0 +1,260 @@
+//! External programs, for producers whose numbers only a tool can tell. Only the programs
+//! allowed under `[globals.commands]` in `badger.toml` run, each with a time limit and with
+//! its output capped.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use steel::SteelErr;
+use steel::SteelVal;
+use steel::rerrs::ErrorKind;
+use steel::rvals::IntoSteelVal;
+use steel::steel_vm::{engine::Engine, register_fn::RegisterFn};
+
+use super::fs::confined;
+use crate::badger::Commands;
+use crate::cache::Inputs;
+
+/// How often a running command is checked for having exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Register `run-command` against the project at `root`, under the `commands` policy:
+///
+/// - `(run-command program args dir timeout)`: run `program` with the list of string `args`
+///   in `dir`, relative to the project root, or the root itself for `#f`. `timeout` is in
+///   whole seconds, at least 1, and may only shorten the configured limit; `#f` uses it as
+///   is. Returns a hash of `stdout`, `stderr`, `exit-code` (`#f` when killed by a signal)
+///   and `truncated`, which is `#t` when either stream went over `max_output`. A program
+///   that is not in `allow`, spelled exactly as there, or that runs out of time, is an
+///   error. What a command reads is unknown, so a producer that runs one is marked
+///   volatile in `inputs` and never served from the cache.
+pub(crate) fn register(engine: &mut Engine, root: &Path, commands: &Commands, inputs: &Inputs) {
+    let root = root.to_path_buf();
+    let inputs = inputs.clone();
+    let allow = commands.allow.clone();
+    let limit = commands.timeout.to_std();
+    let max_output = commands.max_output;
+
+    engine.register_fn(
+        "run-command",
+        move |program: String,
+              args: Vec<String>,
+              dir: Option<String>,
+              timeout: Option<isize>|
+              -> Result<SteelVal, SteelErr> {
+            if !allow.contains(&program) {
+                return Err(SteelErr::new(
+                    ErrorKind::Generic,
+                    format!(
+                        "`{program}` may not run; add it to `allow` under [globals.commands] in badger.toml"
+                    ),
+                ));
+            }
+
+            inputs.volatile();
+            let dir = confined(&root, dir.as_deref().unwrap_or("."))?;
+            let timeout = match timeout {
+                // No command finishes within zero seconds.
+                Some(seconds) if seconds < 1 => {
+                    return Err(SteelErr::new(
+                        ErrorKind::Generic,
+                        format!("timeout must be at least 1 second, found {seconds}"),
+                    ));
+                }
+                Some(seconds) => Duration::from_secs(seconds as u64).min(limit),
+                None => limit,
+            };
+
+            run(&program, &args, &dir, timeout, max_output)?.into_steelval()
+        },
+    );
+}
+
+/// What a finished command left behind.
+#[derive(Debug)]
+struct Output {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    exit_code: Option<i32>,
+    truncated: bool,
+}
+
+fn run(
+    program: &str,
+    args: &[String],
+    dir: &Path,
+    timeout: Duration,
+    max_output: usize,
+) -> Result<Output, SteelErr> {
+    let command_line = std::iter::once(program)
+        .chain(args.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let io_error =
+        |e: std::io::Error| SteelErr::new(ErrorKind::Io, format!("`{command_line}`: {e}"));
+
+    let mut child = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(io_error)?;
+
+    // Both pipes are drained as the command runs, so it never blocks on a full one.
+    let stdout = capture(child.stdout.take(), max_output);
+    let stderr = capture(child.stderr.take(), max_output);
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(io_error)? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            // The readers are left behind: a grandchild may still hold the pipes open.
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(SteelErr::new(
+                ErrorKind::Generic,
+                format!(
+                    "`{command_line}` did not finish within {}s",
+                    timeout.as_secs()
+                ),
+            ));
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    let (stdout, stdout_truncated) = stdout.join().unwrap_or_default();
+    let (stderr, stderr_truncated) = stderr.join().unwrap_or_default();
+
+    Ok(Output {
+        stdout,
+        stderr,
+        exit_code: status.code(),
+        truncated: stdout_truncated || stderr_truncated,
+    })
+}
+
+/// Read `stream` to the end on its own thread, keeping the first `max` bytes and whether
+/// anything was dropped.
+fn capture(stream: Option<impl Read + Send + 'static>, max: usize) -> JoinHandle<(Vec<u8>, bool)> {
+    thread::spawn(move || {
+        let mut kept = Vec::new();
+        let mut truncated = false;
+        let Some(mut stream) = stream else {
+            return (kept, truncated);
+        };
+
+        let mut buffer = [0; 8192];
+        loop {
+            match stream.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    let room = max.saturating_sub(kept.len());
+                    kept.extend_from_slice(&buffer[..n.min(room)]);
+                    truncated |= n > room;
+                }
+            }
+        }
+        (kept, truncated)
+    })
+}
+
+impl Output {
+    fn into_steelval(self) -> Result<SteelVal, SteelErr> {
+        HashMap::from([
+            (
+                "stdout".to_string(),
+                String::from_utf8_lossy(&self.stdout)
+                    .into_owned()
+                    .into_steelval()?,
+            ),
+            (
+                "stderr".to_string(),
+                String::from_utf8_lossy(&self.stderr)
+                    .into_owned()
+                    .into_steelval()?,
+            ),
+            ("exit-code".to_string(), self.exit_code.into_steelval()?),
+            ("truncated".to_string(), self.truncated.into_steelval()?),
+        ])
+        .into_steelval()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_sh(script: &str, timeout: Duration, max_output: usize) -> Result<Output, SteelErr> {
+        let args = ["-c".to_string(), script.to_string()];
+        run("sh", &args, Path::new("."), timeout, max_output)
+    }
+
+    #[test]
+    fn refuses_programs_outside_the_allow_list() {
+        let commands = Commands {
+            allow: vec!["sh".to_string()],
+            ..Commands::default()
+        };
+        let root = std::env::temp_dir().canonicalize().unwrap();
+        let mut engine = Engine::new_sandboxed();
+        register(&mut engine, &root, &commands, &Inputs::default());
+
+        for program in ["/bin/sh", "bash", "sh "] {
+            let error = engine
+                .run(format!("(run-command {program:?} '() #f #f)"))
+                .unwrap_err();
+            assert!(
+                error.to_string().contains("may not run"),
+                "{program}: {error}"
+            );
+        }
+        let error = engine
+            .run(r#"(run-command "sh" '("-c" "true") #f 0)"#)
+            .unwrap_err();
+        assert!(error.to_string().contains("at least 1 second"), "{error}");
+        assert!(
+            engine
+                .run(r#"(run-command "sh" '("-c" "true") #f 1)"#)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn kills_a_command_that_runs_out_of_time() {
+        let started = Instant::now();
+        let error = run_sh("sleep 10", Duration::from_secs(1), 1024).unwrap_err();
+
+        assert!(
+            error.to_string().contains("did not finish within 1s"),
+            "{error}"
+        );
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn caps_each_stream_at_max_output() {
+        let output = run_sh(
+            "printf 0123456789; printf ab >&2; exit 3",
+            Duration::from_secs(5),
+            4,
+        )
+        .unwrap();
+
+        assert_eq!(output.stdout, b"0123");
+        assert_eq!(output.stderr, b"ab");
+        assert_eq!(output.exit_code, Some(3));
+        assert!(output.truncated);
+
+        let output = run_sh("printf 0123", Duration::from_secs(5), 4).unwrap();
+        assert_eq!(output.stdout, b"0123");
+        assert!(!output.truncated);
+    }
+}
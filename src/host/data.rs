@@ -0,0 +1,231 @@
+//! Structured data files of the project, and a small query language to pick values out of
+//! them: `tool.poetry.version`, `$.engines.node`, `workspaces[0]`, `deps["serde.json"]`.
+
+use std::path::Path;
+
+use steel::SteelErr;
+use steel::SteelVal;
+use steel::rerrs::ErrorKind;
+use steel::steel_vm::{engine::Engine, register_fn::RegisterFn};
+
+use super::fs::confined;
+use crate::cache::Inputs;
+use crate::wrappers::json::parse_json;
+use crate::wrappers::toml::toml_value_to_steelval;
+use crate::wrappers::xml::parse_xml;
+use crate::wrappers::yaml::parse_yaml;
+
+/// Register the data host functions against the project at `root`, recording the files
+/// they read in `inputs`:
+///
+/// - `(read-data path format)`: the file at `path` parsed as `toml`, `json`, `yaml` or
+///   `xml`, or by its extension for `auto`, as by `parse-toml` and friends.
+/// - `(data-ref data query)`: the value `query` selects in `data`. Fails, naming the
+///   first missing step, when there is none.
+pub(crate) fn register(engine: &mut Engine, root: &Path, inputs: &Inputs) {
+    let root = root.to_path_buf();
+    let inputs = inputs.clone();
+    engine.register_fn(
+        "read-data",
+        move |path: String, format: String| -> Result<SteelVal, SteelErr> {
+            let file = confined(&root, &path)?;
+            let text = inputs.read_to_string(&file).map_err(|e| {
+                SteelErr::new(
+                    ErrorKind::Io,
+                    format!("cannot read {}: {e}", file.display()),
+                )
+            })?;
+
+            parse(&path, &format, text)
+                .map_err(|e| SteelErr::new(ErrorKind::Generic, format!("{}: {e}", file.display())))
+        },
+    );
+
+    engine.register_fn(
+        "data-ref",
+        |data: SteelVal, query: String| -> Result<SteelVal, SteelErr> {
+            select(data, &query)
+                .map_err(|e| SteelErr::new(ErrorKind::Generic, format!("query `{query}`: {e}")))
+        },
+    );
+}
+
+fn parse(path: &str, format: &str, text: String) -> Result<SteelVal, String> {
+    let format = match format {
+        "auto" => match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => "toml",
+            Some("json") => "json",
+            Some("yaml" | "yml") => "yaml",
+            Some("xml") => "xml",
+            _ => return Err("cannot tell the format from the extension; set it".to_string()),
+        },
+        "toml" | "json" | "yaml" | "xml" => format,
+        other => {
+            return Err(format!(
+                "unknown data format `{other}`; expected toml, json, yaml, xml or auto"
+            ));
+        }
+    };
+
+    match format {
+        "toml" => {
+            let value: toml::Value = toml::from_str(&text).map_err(|e| e.to_string())?;
+            toml_value_to_steelval(value)
+        }
+        "json" => parse_json(text),
+        "xml" => parse_xml(text),
+        _ => parse_yaml(text),
+    }
+}
+
+/// One step of a query.
+#[derive(Debug)]
+enum Step {
+    Key(String),
+    Index(usize),
+}
+
+/// Split a query into steps. A leading `$` is allowed; keys are separated by `.`, and
+/// `[n]` indexes a list while `["key"]` or `['key']` names a key containing dots.
+fn parse_query(query: &str) -> Result<Vec<Step>, String> {
+    let mut rest = query.trim();
+    rest = rest.strip_prefix('$').unwrap_or(rest);
+    let mut steps = Vec::new();
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(|| "unclosed `[`".to_string())?;
+            let inside = after[..end].trim();
+
+            let quoted = ['"', '\'']
+                .iter()
+                .find_map(|q| inside.strip_prefix(*q).and_then(|s| s.strip_suffix(*q)));
+            steps.push(match quoted {
+                Some(key) => Step::Key(key.to_string()),
+                None => Step::Index(
+                    inside
+                        .parse()
+                        .map_err(|_| format!("`[{inside}]` is not a list index"))?,
+                ),
+            });
+            rest = &after[end + 1..];
+        } else {
+            rest = rest.strip_prefix('.').unwrap_or(rest);
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            if end == 0 {
+                return Err("empty key".to_string());
+            }
+            steps.push(Step::Key(rest[..end].to_string()));
+            rest = &rest[end..];
+        }
+    }
+
+    Ok(steps)
+}
+
+fn select(data: SteelVal, query: &str) -> Result<SteelVal, String> {
+    let mut value = data;
+    let mut path = String::from("$");
+
+    for step in parse_query(query)? {
+        let next = match (&step, &value) {
+            (Step::Key(key), SteelVal::HashMapV(map)) => {
+                map.get(&SteelVal::StringV(key.as_str().into())).cloned()
+            }
+            (Step::Index(i), SteelVal::ListV(list)) => list.get(*i).cloned(),
+            (Step::Index(i), SteelVal::VectorV(vector)) => vector.get(*i).cloned(),
+            (Step::Key(key), _) => {
+                return Err(format!("`{path}` is not a table, so it has no key `{key}`"));
+            }
+            (Step::Index(i), _) => {
+                return Err(format!("`{path}` is not a list, so it has no index {i}"));
+            }
+        };
+
+        match &step {
+            Step::Key(key) => path = format!("{path}.{key}"),
+            Step::Index(i) => path = format!("{path}[{i}]"),
+        }
+        value = next.ok_or_else(|| format!("nothing at `{path}`"))?;
+    }
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data() -> SteelVal {
+        parse(
+            "package.json",
+            "auto",
+            r#"{
+                "engines": {"node": ">=18"},
+                "workspaces": ["app", "lib"],
+                "deps": {"serde.json": "1.0"},
+                "matrix": [[1, 2], [3, 4]]
+            }"#
+            .to_string(),
+        )
+        .unwrap()
+    }
+
+    fn query(query: &str) -> Result<SteelVal, String> {
+        select(data(), query)
+    }
+
+    #[test]
+    fn selects_keys_and_indexes() {
+        let text = |s: &str| Ok(SteelVal::StringV(s.into()));
+
+        assert_eq!(query("engines.node"), text(">=18"));
+        assert_eq!(query("$.engines.node"), text(">=18"));
+        assert_eq!(query(" workspaces[1] "), text("lib"));
+        assert_eq!(query(r#"deps["serde.json"]"#), text("1.0"));
+        assert_eq!(query("deps['serde.json']"), text("1.0"));
+        assert_eq!(query("matrix[1][0]"), Ok(SteelVal::IntV(3)));
+        assert_eq!(query("$"), Ok(data()));
+    }
+
+    #[test]
+    fn names_the_step_that_fails() {
+        assert_eq!(
+            query("engines.npm"),
+            Err("nothing at `$.engines.npm`".to_string())
+        );
+        assert_eq!(
+            query("workspaces[2]"),
+            Err("nothing at `$.workspaces[2]`".to_string())
+        );
+        assert_eq!(
+            query("engines.node.major"),
+            Err("`$.engines.node` is not a table, so it has no key `major`".to_string())
+        );
+        assert_eq!(
+            query("engines[0]"),
+            Err("`$.engines` is not a list, so it has no index 0".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_queries() {
+        for malformed in [
+            "engines..node",
+            "engines.",
+            "workspaces[0",
+            "workspaces[-1]",
+            "[x]",
+        ] {
+            assert!(parse_query(malformed).is_err(), "{malformed}");
+        }
+    }
+
+    #[test]
+    fn picks_the_format_by_extension() {
+        assert!(parse("Cargo.toml", "auto", "[package]\nname = 'a'".to_string()).is_ok());
+        assert!(parse("config.yml", "auto", "a: 1".to_string()).is_ok());
+        assert!(parse("notes.txt", "auto", String::new()).is_err());
+        assert!(parse("a.json", "ini", "{}".to_string()).is_err());
+    }
+}
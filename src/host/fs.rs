@@ -0,0 +1,195 @@
+//! `badger/fs`: file access for plugins, confined to the project root. Plugins load it with
+//! `(require-builtin badger/fs)`; the engine itself is sandboxed, so this is the only way
+//! they reach the filesystem.
+
+use std::path::{Component, Path, PathBuf};
+
+use steel::SteelErr;
+use steel::rerrs::ErrorKind;
+use steel::steel_vm::builtin::BuiltInModule;
+use steel::steel_vm::{engine::Engine, register_fn::RegisterFn};
+
+use crate::cache::Inputs;
+
+/// Resolve `path` against `root`, following symlinks as far as the path exists, and refuse
+/// anything that ends up outside `root`. `root` must already be canonical.
+pub(crate) fn confine(root: &Path, path: impl AsRef<Path>) -> Result<PathBuf, String> {
+    let path = path.as_ref();
+
+    let mut lexical = PathBuf::new();
+    for component in root.join(path).components() {
+        match component {
+            Component::ParentDir => {
+                lexical.pop();
+            }
+            Component::CurDir => {}
+            other => lexical.push(other),
+        }
+    }
+
+    let existing = lexical.ancestors().find(|dir| dir.exists()).unwrap_or(root);
+    let mut resolved = existing
+        .canonicalize()
+        .map_err(|e| format!("cannot resolve {}: {e}", existing.display()))?;
+    if let Ok(rest) = lexical.strip_prefix(existing)
+        && !rest.as_os_str().is_empty()
+    {
+        resolved.push(rest);
+    }
+
+    if resolved.starts_with(root) {
+        Ok(resolved)
+    } else {
+        Err(format!("{} is outside the project root", path.display()))
+    }
+}
+
+/// [`confine`] for host functions, as a Steel error.
+pub(crate) fn confined(root: &Path, path: impl AsRef<Path>) -> Result<PathBuf, SteelErr> {
+    confine(root, path).map_err(|e| SteelErr::new(ErrorKind::Io, e))
+}
+
+/// `path` relative to `root`, as plugins see it.
+fn relative(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Register the `badger/fs` module against the project at `root`, recording what it reads
+/// in `inputs`. Every path is relative to the project root and may not leave it:
+///
+/// - `(read-file path)`: the file's contents as a string.
+/// - `(file-exists? path)`: whether `path` is an existing file or directory.
+/// - `(glob pattern)`: the sorted paths matching `pattern`, such as `crates/*/Cargo.toml`.
+/// - `(walk-dir dir)`: every file under `dir`, sorted, skipping hidden files and those
+///   ignored by `.gitignore`.
+/// - `(manifest-dir)`: the absolute path of the project root, where its configuration lives.
+pub(crate) fn register(engine: &mut Engine, root: &Path, inputs: &Inputs) {
+    let mut module = BuiltInModule::new("badger/fs");
+
+    let read_root = root.to_path_buf();
+    let read_inputs = inputs.clone();
+    module.register_fn(
+        "read-file",
+        move |path: String| -> Result<String, SteelErr> {
+            let file = confined(&read_root, &path)?;
+            read_inputs
+                .read_to_string(&file)
+                .map_err(|e| SteelErr::new(ErrorKind::Io, format!("cannot read {path}: {e}")))
+        },
+    );
+
+    let exists_root = root.to_path_buf();
+    let exists_inputs = inputs.clone();
+    module.register_fn(
+        "file-exists?",
+        move |path: String| -> Result<bool, SteelErr> {
+            Ok(exists_inputs.exists(&confined(&exists_root, &path)?))
+        },
+    );
+
+    let glob_root = root.to_path_buf();
+    let glob_inputs = inputs.clone();
+    module.register_fn(
+        "glob",
+        move |pattern: String| -> Result<Vec<String>, SteelErr> {
+            // The root is escaped so a project under `[ci]` or `*` matches only itself.
+            let root = glob::Pattern::escape(&glob_root.to_string_lossy());
+            let full = Path::new(&root).join(&pattern);
+            let matches = glob_inputs.glob(&full.to_string_lossy()).map_err(|e| {
+                SteelErr::new(
+                    ErrorKind::Generic,
+                    format!("invalid pattern {pattern:?}: {e}"),
+                )
+            })?;
+
+            let mut paths = Vec::new();
+            for path in matches {
+                // Matches that leave the root through `..` or a symlink are dropped, not errors.
+                if let Ok(path) = confine(&glob_root, &path) {
+                    paths.push(relative(&glob_root, &path));
+                }
+            }
+            paths.sort();
+            Ok(paths)
+        },
+    );
+
+    let walk_root = root.to_path_buf();
+    let walk_inputs = inputs.clone();
+    module.register_fn(
+        "walk-dir",
+        move |dir: String| -> Result<Vec<String>, SteelErr> {
+            let dir = confined(&walk_root, &dir)?;
+            let files = walk_inputs
+                .walk(&dir)
+                .map_err(|e| SteelErr::new(ErrorKind::Io, e.to_string()))?;
+
+            Ok(files
+                .iter()
+                .map(|file| relative(&walk_root, file))
+                .collect())
+        },
+    );
+
+    let manifest_root = root.to_path_buf();
+    module.register_fn("manifest-dir", move || {
+        manifest_root.to_string_lossy().into_owned()
+    });
+
+    engine.register_module(module);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A canonical project root holding `file`, `sub/` and a symlink `escape` to `outside/`.
+    fn project(name: &str) -> (PathBuf, PathBuf) {
+        let base = std::env::temp_dir().join(format!("badger-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        std::fs::create_dir_all(base.join("root/sub")).unwrap();
+        std::fs::create_dir_all(base.join("outside")).unwrap();
+        std::fs::write(base.join("root/file"), "").unwrap();
+        std::fs::write(base.join("outside/secret"), "").unwrap();
+        std::os::unix::fs::symlink(base.join("outside"), base.join("root/escape")).unwrap();
+        let root = base.join("root").canonicalize().unwrap();
+        (base, root)
+    }
+
+    #[test]
+    fn keeps_paths_inside_the_root() {
+        let (base, root) = project("confine-inside");
+
+        assert_eq!(confine(&root, "file"), Ok(root.join("file")));
+        assert_eq!(confine(&root, "./sub/../file"), Ok(root.join("file")));
+        assert_eq!(
+            confine(&root, "sub/new/missing"),
+            Ok(root.join("sub/new/missing"))
+        );
+        assert_eq!(confine(&root, root.join("sub")), Ok(root.join("sub")));
+        assert_eq!(confine(&root, ""), Ok(root.clone()));
+
+        std::fs::remove_dir_all(base).unwrap();
+    }
+
+    #[test]
+    fn refuses_paths_that_leave_the_root() {
+        let (base, root) = project("confine-outside");
+
+        for path in [
+            "..",
+            "../outside/secret",
+            "sub/../../outside",
+            "escape/secret",
+            "escape/missing",
+            "/etc/passwd",
+        ] {
+            assert!(confine(&root, path).is_err(), "{path}");
+        }
+
+        std::fs::remove_dir_all(base).unwrap();
+    }
+}
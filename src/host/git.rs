@@ -0,0 +1,242 @@
+//! Read-only queries against the project's local git repository. Nothing here touches the
+//! network: every function runs a plumbing command of the local `git` binary.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use semver::Version;
+use steel::SteelErr;
+use steel::SteelVal;
+use steel::rerrs::ErrorKind;
+use steel::rvals::IntoSteelVal;
+use steel::steel_vm::{engine::Engine, register_fn::RegisterFn};
+
+use crate::cache::Inputs;
+
+/// Register the git host functions against the repository containing `root`, recording
+/// the state of the repository in `inputs` whenever they query it:
+///
+/// - `(git-last-commit)`: a hash of `hash`, `date` (`YYYY-MM-DD`), `timestamp`, `author`
+///   and `subject` for the commit at `HEAD`.
+/// - `(git-commit-count rev)`: the number of commits reachable from `rev`, or `HEAD` for
+///   `#f`. Ranges such as `v1.0.0..HEAD` count the commits since a tag.
+/// - `(git-latest-tag prereleases?)`: the highest tag that parses as a semantic version,
+///   with or without a leading `v`, or `#f` when there is none.
+/// - `(git-authors)`: the distinct author names on `HEAD`, honouring `.mailmap`.
+pub(crate) fn register(engine: &mut Engine, root: &Path, inputs: &Inputs) {
+    let repo = Repo {
+        dir: root.to_path_buf(),
+        inputs: inputs.clone(),
+    };
+    engine.register_fn("git-last-commit", move || repo.last_commit());
+
+    let repo = Repo {
+        dir: root.to_path_buf(),
+        inputs: inputs.clone(),
+    };
+    engine.register_fn("git-commit-count", move |rev: Option<String>| {
+        repo.commit_count(rev.as_deref().unwrap_or("HEAD"))
+    });
+
+    let repo = Repo {
+        dir: root.to_path_buf(),
+        inputs: inputs.clone(),
+    };
+    engine.register_fn("git-latest-tag", move |prereleases: bool| {
+        repo.latest_tag(prereleases)
+    });
+
+    let repo = Repo {
+        dir: root.to_path_buf(),
+        inputs: inputs.clone(),
+    };
+    engine.register_fn("git-authors", move || repo.authors());
+}
+
+struct Repo {
+    dir: PathBuf,
+    inputs: Inputs,
+}
+
+impl Repo {
+    /// Run `git` in the project and return its trimmed standard output.
+    fn git(&self, args: &[&str]) -> Result<String, SteelErr> {
+        self.inputs.git(&self.dir);
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.dir)
+            .args(args)
+            .output()
+            .map_err(|e| SteelErr::new(ErrorKind::Io, format!("cannot run git: {e}")))?;
+
+        if !output.status.success() {
+            return Err(SteelErr::new(
+                ErrorKind::Generic,
+                format!(
+                    "git {} failed: {}",
+                    args.join(" "),
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn last_commit(&self) -> Result<SteelVal, SteelErr> {
+        // Fields are separated by NUL so subjects and names may contain anything else.
+        let log = self.git(&["log", "-1", "--format=%H%x00%cs%x00%ct%x00%aN%x00%s"])?;
+        let fields: Vec<&str> = log.split('\0').collect();
+        let [hash, date, timestamp, author, subject] = fields[..] else {
+            return Err(SteelErr::new(
+                ErrorKind::Generic,
+                format!("unexpected git log output: {log:?}"),
+            ));
+        };
+
+        let timestamp: i64 = timestamp.parse().map_err(|_| {
+            SteelErr::new(
+                ErrorKind::Generic,
+                format!("invalid commit time {timestamp:?}"),
+            )
+        })?;
+
+        HashMap::from([
+            ("hash".to_string(), hash.into_steelval()?),
+            ("date".to_string(), date.into_steelval()?),
+            ("timestamp".to_string(), timestamp.into_steelval()?),
+            ("author".to_string(), author.into_steelval()?),
+            ("subject".to_string(), subject.into_steelval()?),
+        ])
+        .into_steelval()
+    }
+
+    fn commit_count(&self, rev: &str) -> Result<i64, SteelErr> {
+        let count = self.git(&["rev-list", "--count", "--end-of-options", rev, "--"])?;
+        count.parse().map_err(|_| {
+            SteelErr::new(
+                ErrorKind::Generic,
+                format!("invalid commit count {count:?}"),
+            )
+        })
+    }
+
+    fn latest_tag(&self, prereleases: bool) -> Result<Option<String>, SteelErr> {
+        let tags = self.git(&["tag", "--list"])?;
+
+        Ok(tags
+            .lines()
+            .filter_map(|tag| {
+                let version = Version::parse(tag.strip_prefix('v').unwrap_or(tag)).ok()?;
+                (prereleases || version.pre.is_empty()).then_some((version, tag))
+            })
+            .max()
+            .map(|(_, tag)| tag.to_string()))
+    }
+
+    fn authors(&self) -> Result<Vec<String>, SteelErr> {
+        let authors = self.git(&["log", "--format=%aN"])?;
+        // Names depend on `.mailmap` as well as on the history, so it counts as read.
+        let top = self.git(&["rev-parse", "--show-toplevel"])?;
+        let _ = self
+            .inputs
+            .read_to_string(&Path::new(&top).join(".mailmap"));
+        let distinct: BTreeSet<&str> = authors.lines().collect();
+
+        Ok(distinct.into_iter().map(str::to_string).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args([
+                "-c",
+                "user.email=dev@example.com",
+                "-c",
+                "commit.gpgsign=false",
+            ])
+            .args(args)
+            .env("GIT_AUTHOR_DATE", "2024-03-01T12:00:00Z")
+            .env("GIT_COMMITTER_DATE", "2024-03-01T12:00:00Z")
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?}");
+    }
+
+    fn commit(dir: &Path, author: &str, subject: &str) {
+        run(
+            dir,
+            &[
+                "-c",
+                &format!("user.name={author}"),
+                "commit",
+                "--allow-empty",
+                "-qm",
+                subject,
+            ],
+        );
+    }
+
+    fn fixture(name: &str) -> Repo {
+        let dir = std::env::temp_dir().join(format!("badger-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        run(&dir, &["init", "-q"]);
+
+        commit(&dir, "Ada", "first");
+        run(&dir, &["tag", "v1.0.0"]);
+        commit(&dir, "Grace", "second");
+        run(&dir, &["tag", "v1.1.0-rc.1"]);
+        run(&dir, &["tag", "not-a-version"]);
+        commit(&dir, "Ada", "third: with\ttabs");
+
+        Repo {
+            dir,
+            inputs: Inputs::default(),
+        }
+    }
+
+    #[test]
+    fn counts_commits_and_picks_the_highest_version_tag() {
+        let repo = fixture("git-tags");
+
+        assert_eq!(repo.commit_count("HEAD").unwrap(), 3);
+        assert_eq!(repo.commit_count("v1.0.0..HEAD").unwrap(), 2);
+        assert!(repo.commit_count("--all").is_err());
+
+        assert_eq!(repo.latest_tag(false).unwrap().as_deref(), Some("v1.0.0"));
+        assert_eq!(
+            repo.latest_tag(true).unwrap().as_deref(),
+            Some("v1.1.0-rc.1")
+        );
+        assert_eq!(repo.authors().unwrap(), ["Ada", "Grace"]);
+
+        std::fs::remove_dir_all(&repo.dir).unwrap();
+    }
+
+    #[test]
+    fn describes_the_last_commit() {
+        let repo = fixture("git-last");
+        let SteelVal::HashMapV(fields) = repo.last_commit().unwrap() else {
+            panic!("expected a hash");
+        };
+        let field = |key: &str| fields.get(&SteelVal::StringV(key.into())).cloned();
+
+        assert_eq!(field("date"), Some(SteelVal::StringV("2024-03-01".into())));
+        assert_eq!(field("timestamp"), Some(SteelVal::IntV(1_709_294_400)));
+        assert_eq!(field("author"), Some(SteelVal::StringV("Ada".into())));
+        assert_eq!(
+            field("subject"),
+            Some(SteelVal::StringV("third: with\ttabs".into()))
+        );
+
+        std::fs::remove_dir_all(&repo.dir).unwrap();
+    }
+}
@@ -0,0 +1,313 @@
+//! `count-lines`: code, comment and blank line counts per language, walked natively so
+//! plugins never have to read every file of a project themselves.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use steel::SteelErr;
+use steel::rerrs::ErrorKind;
+use steel::steel_vm::{engine::Engine, register_fn::RegisterFn};
+use tracing::debug;
+
+use super::fs::confined;
+use crate::cache::Inputs;
+
+/// How a language writes comments.
+struct Language {
+    name: &'static str,
+    extensions: &'static [&'static str],
+    line: &'static [&'static str],
+    block: Option<(&'static str, &'static str)>,
+}
+
+const C_STYLE: Option<(&str, &str)> = Some(("/*", "*/"));
+
+const LANGUAGES: &[Language] = &[
+    Language {
+        name: "Rust",
+        extensions: &["rs"],
+        line: &["//"],
+        block: C_STYLE,
+    },
+    Language {
+        name: "Scheme",
+        extensions: &["scm", "ss", "rkt"],
+        line: &[";"],
+        block: Some(("#|", "|#")),
+    },
+    Language {
+        name: "TOML",
+        extensions: &["toml"],
+        line: &["#"],
+        block: None,
+    },
+    Language {
+        name: "Markdown",
+        extensions: &["md", "markdown"],
+        line: &[],
+        block: Some(("<!--", "-->")),
+    },
+    Language {
+        name: "C",
+        extensions: &["c", "h"],
+        line: &["//"],
+        block: C_STYLE,
+    },
+    Language {
+        name: "C++",
+        extensions: &["cc", "cpp", "cxx", "hpp", "hh"],
+        line: &["//"],
+        block: C_STYLE,
+    },
+    Language {
+        name: "Go",
+        extensions: &["go"],
+        line: &["//"],
+        block: C_STYLE,
+    },
+    Language {
+        name: "Java",
+        extensions: &["java"],
+        line: &["//"],
+        block: C_STYLE,
+    },
+    Language {
+        name: "JavaScript",
+        extensions: &["js", "mjs", "cjs", "jsx"],
+        line: &["//"],
+        block: C_STYLE,
+    },
+    Language {
+        name: "TypeScript",
+        extensions: &["ts", "tsx"],
+        line: &["//"],
+        block: C_STYLE,
+    },
+    Language {
+        name: "CSS",
+        extensions: &["css"],
+        line: &[],
+        block: C_STYLE,
+    },
+    Language {
+        name: "HTML",
+        extensions: &["html", "htm"],
+        line: &[],
+        block: Some(("<!--", "-->")),
+    },
+    Language {
+        name: "Python",
+        extensions: &["py"],
+        line: &["#"],
+        block: None,
+    },
+    Language {
+        name: "Shell",
+        extensions: &["sh", "bash", "zsh"],
+        line: &["#"],
+        block: None,
+    },
+    Language {
+        name: "Lua",
+        extensions: &["lua"],
+        line: &["--"],
+        block: Some(("--[[", "]]")),
+    },
+    Language {
+        name: "YAML",
+        extensions: &["yml", "yaml"],
+        line: &["#"],
+        block: None,
+    },
+    Language {
+        name: "JSON",
+        extensions: &["json"],
+        line: &[],
+        block: None,
+    },
+];
+
+/// Line counts for one language.
+#[derive(Debug, Default, Clone, Copy)]
+struct Counts {
+    files: i64,
+    code: i64,
+    comment: i64,
+    blank: i64,
+}
+
+impl Counts {
+    fn add(&mut self, other: Counts) {
+        self.files += other.files;
+        self.code += other.code;
+        self.comment += other.comment;
+        self.blank += other.blank;
+    }
+
+    fn into_map(self) -> HashMap<String, i64> {
+        HashMap::from([
+            ("files".to_string(), self.files),
+            ("code".to_string(), self.code),
+            ("comment".to_string(), self.comment),
+            ("blank".to_string(), self.blank),
+        ])
+    }
+}
+
+/// Register `(count-lines dir)`, which walks `dir` (relative to `root`, or `root` itself
+/// for `#f`) and returns a hash of language name to `files`, `code`, `comment` and `blank`
+/// counts. Files ignored by `.gitignore`, hidden files and unknown file types are skipped.
+/// The walk and every file counted are recorded in `inputs`.
+pub(crate) fn register(engine: &mut Engine, root: &Path, inputs: &Inputs) {
+    let root = root.to_path_buf();
+    let inputs = inputs.clone();
+
+    engine.register_fn(
+        "count-lines",
+        move |dir: Option<String>| -> Result<HashMap<String, HashMap<String, i64>>, SteelErr> {
+            let dir = match dir {
+                Some(dir) => confined(&root, dir)?,
+                None => root.clone(),
+            };
+
+            count_lines(&inputs, &dir)
+                .map(|counts| {
+                    counts
+                        .into_iter()
+                        .map(|(k, v)| (k.to_string(), v.into_map()))
+                        .collect()
+                })
+                .map_err(|e| SteelErr::new(ErrorKind::Io, e))
+        },
+    );
+}
+
+fn count_lines(inputs: &Inputs, dir: &Path) -> Result<HashMap<&'static str, Counts>, String> {
+    let mut totals: HashMap<&'static str, Counts> = HashMap::new();
+
+    for path in inputs.walk(dir).map_err(|e| e.to_string())? {
+        let Some(language) = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| LANGUAGES.iter().find(|l| l.extensions.contains(&ext)))
+        else {
+            continue;
+        };
+
+        // Binary or non UTF-8 files that happen to carry a known extension are skipped.
+        let Ok(text) = inputs.read_to_string(&path) else {
+            debug!(path = %path.display(), "skipping unreadable file");
+            continue;
+        };
+
+        totals
+            .entry(language.name)
+            .or_default()
+            .add(count_file(language, &text));
+    }
+
+    Ok(totals)
+}
+
+fn count_file(language: &Language, text: &str) -> Counts {
+    let mut counts = Counts {
+        files: 1,
+        ..Counts::default()
+    };
+    let mut in_block = false;
+
+    for line in text.lines() {
+        let line = line.trim();
+        let (code, still_in_block) = scan(language, line, in_block);
+        in_block = still_in_block;
+
+        if line.is_empty() {
+            counts.blank += 1;
+        } else if code {
+            counts.code += 1;
+        } else {
+            counts.comment += 1;
+        }
+    }
+
+    counts
+}
+
+/// Whether `line` holds any code, and whether a block comment is open after it. Comments
+/// are only recognised where a line starts or a block comment has just closed, so markers
+/// inside string literals, such as the `/*` of `"crates/*"`, are left alone. Block markers
+/// are checked first, since Lua's `--[[` also starts with its line comment `--`.
+fn scan(language: &Language, mut rest: &str, mut in_block: bool) -> (bool, bool) {
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return (false, in_block);
+        }
+
+        match language.block {
+            Some((_, end)) if in_block => match rest.find(end) {
+                Some(i) => {
+                    rest = &rest[i + end.len()..];
+                    in_block = false;
+                }
+                None => return (false, true),
+            },
+            Some((start, _)) if rest.starts_with(start) => {
+                rest = &rest[start.len()..];
+                in_block = true;
+            }
+            _ if language.line.iter().any(|prefix| rest.starts_with(prefix)) => {
+                return (false, false);
+            }
+            _ => return (true, false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(extension: &str, text: &str) -> (i64, i64, i64) {
+        let language = LANGUAGES
+            .iter()
+            .find(|l| l.extensions.contains(&extension))
+            .unwrap();
+        let counts = count_file(language, text);
+        (counts.code, counts.comment, counts.blank)
+    }
+
+    #[test]
+    fn classifies_code_comment_and_blank_lines() {
+        let rust = "\
+// line comment
+fn main() {
+
+    /* a block
+       over lines */
+    /* closed */ let a = 1;
+    */ // a stray close is code
+    let glob = \"crates/*/Cargo.toml\";
+}
+";
+        assert_eq!(count("rs", rust), (5, 3, 1));
+        assert_eq!(count("rs", "/* one */ /* two\n*/ main();\n"), (1, 1, 0));
+        assert_eq!(
+            count("md", "# Title\n<!-- note -->\n\n<!--\nhidden\n--> text\n"),
+            (2, 3, 1)
+        );
+    }
+
+    #[test]
+    fn reads_lua_block_comments_before_line_comments() {
+        let lua = "\
+-- line comment
+--[[ block
+print(\"still a comment\")
+]]
+--[[ closed ]] print(1)
+local x = 1 -- trailing
+";
+        assert_eq!(count("lua", lua), (2, 4, 0));
+    }
+}
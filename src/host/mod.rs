@@ -0,0 +1,10 @@
+//! Native helpers exposed to plugins. Each module registers its functions on the engine
+//! against the project root, so plugins never depend on the working directory.
+
+pub mod cargo;
+pub mod command;
+pub mod data;
+pub mod fs;
+pub mod git;
+pub mod loc;
+pub mod reports;
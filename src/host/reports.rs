@@ -0,0 +1,514 @@
+//! Parsers for the reports CI jobs leave on disk, exposed to plugins as plain hashes.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+
+use steel::SteelErr;
+use steel::SteelVal;
+use steel::rerrs::ErrorKind;
+use steel::rvals::IntoSteelVal;
+use steel::steel_vm::{engine::Engine, register_fn::RegisterFn};
+
+use super::fs::confined;
+use crate::cache::Inputs;
+use crate::wrappers::xml::parse_document;
+
+/// Register the report readers against the project at `root`, recording the reports they
+/// read in `inputs`:
+///
+/// - `(read-coverage path format)`: line and branch coverage from an `lcov.info`, a
+///   Cobertura XML report or `cargo llvm-cov --json` output. `format` is one of `lcov`,
+///   `cobertura`, `llvm-cov` or `auto`. Returns a hash of `format`, `line-percent`,
+///   `branch-percent` (`#f` without branch data), and the `lines-covered`, `lines-total`,
+///   `branches-covered` and `branches-total` counts where the report has them.
+/// - `(read-test-results path format)`: test outcomes from a JUnit XML report or the
+///   libtest JSON output of `cargo test -- -Z unstable-options --format json`. `format` is
+///   one of `junit`, `libtest` or `auto`. Returns a hash of `format`, `passed`, `failed`,
+///   `ignored` and `total`.
+pub(crate) fn register(engine: &mut Engine, root: &Path, inputs: &Inputs) {
+    let coverage_root = root.to_path_buf();
+    let coverage_inputs = inputs.clone();
+    engine.register_fn(
+        "read-coverage",
+        move |path: String, format: String| -> Result<SteelVal, SteelErr> {
+            read_report(&coverage_root, &coverage_inputs, &path, |report| {
+                Coverage::parse(report, &format)
+            })?
+            .into_steelval()
+        },
+    );
+
+    let tests_root = root.to_path_buf();
+    let tests_inputs = inputs.clone();
+    engine.register_fn(
+        "read-test-results",
+        move |path: String, format: String| -> Result<SteelVal, SteelErr> {
+            read_report(&tests_root, &tests_inputs, &path, |report| {
+                TestResults::parse(report, &format)
+            })?
+            .into_steelval()
+        },
+    );
+}
+
+/// Read the report at `path`, relative to `root`, and parse it, naming the file in errors.
+fn read_report<T>(
+    root: &Path,
+    inputs: &Inputs,
+    path: &str,
+    parse: impl FnOnce(&str) -> Result<T, String>,
+) -> Result<T, SteelErr> {
+    let path = confined(root, path)?;
+    let report = inputs.read_to_string(&path).map_err(|e| {
+        SteelErr::new(
+            ErrorKind::Io,
+            format!("cannot read {}: {e}", path.display()),
+        )
+    })?;
+
+    parse(&report)
+        .map_err(|e| SteelErr::new(ErrorKind::Generic, format!("{}: {e}", path.display())))
+}
+
+/// Covered and total counts for lines or branches.
+#[derive(Debug, Default, Clone, Copy)]
+struct Counts {
+    covered: i64,
+    total: i64,
+}
+
+impl Counts {
+    fn percent(self) -> Option<f64> {
+        (self.total > 0).then(|| self.covered as f64 * 100.0 / self.total as f64)
+    }
+}
+
+#[derive(Debug)]
+struct Coverage {
+    format: &'static str,
+    line_percent: f64,
+    branch_percent: Option<f64>,
+    lines: Option<Counts>,
+    branches: Option<Counts>,
+}
+
+impl Coverage {
+    fn parse(report: &str, format: &str) -> Result<Self, String> {
+        let format = match format {
+            "auto" => detect(report),
+            "lcov" | "cobertura" | "llvm-cov" => format,
+            other => {
+                return Err(format!(
+                    "unknown coverage format `{other}`; expected lcov, cobertura, llvm-cov or auto"
+                ));
+            }
+        };
+
+        match format {
+            "cobertura" => Self::cobertura(report),
+            "llvm-cov" => Self::llvm_cov(report),
+            _ => Self::lcov(report),
+        }
+    }
+
+    fn from_counts(format: &'static str, lines: Counts, branches: Counts) -> Result<Self, String> {
+        Ok(Self {
+            format,
+            line_percent: lines
+                .percent()
+                .ok_or_else(|| "the report covers no lines".to_string())?,
+            branch_percent: branches.percent(),
+            lines: Some(lines),
+            branches: (branches.total > 0).then_some(branches),
+        })
+    }
+
+    /// `LF`/`LH` and `BRF`/`BRH` summary records, summed over every source file.
+    fn lcov(report: &str) -> Result<Self, String> {
+        let mut lines = Counts::default();
+        let mut branches = Counts::default();
+
+        for record in report.lines() {
+            let Some((key, value)) = record.trim().split_once(':') else {
+                continue;
+            };
+            let count = || {
+                value
+                    .trim()
+                    .parse::<i64>()
+                    .map_err(|_| format!("invalid lcov record `{record}`"))
+            };
+
+            match key {
+                "LF" => lines.total += count()?,
+                "LH" => lines.covered += count()?,
+                "BRF" => branches.total += count()?,
+                "BRH" => branches.covered += count()?,
+                _ => {}
+            }
+        }
+
+        Self::from_counts("lcov", lines, branches)
+    }
+
+    /// The totals on the root `<coverage>` element. Counts are optional in Cobertura, so the
+    /// rates are used when they are missing.
+    fn cobertura(report: &str) -> Result<Self, String> {
+        let document = parse_document(report)?;
+        let root = document.root_element();
+        if !root.has_tag_name("coverage") {
+            return Err(format!(
+                "expected a <coverage> root element, found <{}>",
+                root.tag_name().name()
+            ));
+        }
+
+        let counts = |covered: &str, total: &str| -> Result<Option<Counts>, String> {
+            Ok(match (attribute(root, covered)?, attribute(root, total)?) {
+                (Some(covered), Some(total)) => Some(Counts { covered, total }),
+                _ => None,
+            })
+        };
+        let percent = |rate: &str| -> Result<Option<f64>, String> {
+            Ok(attribute::<f64>(root, rate)?.map(|rate| rate * 100.0))
+        };
+
+        let lines = counts("lines-covered", "lines-valid")?;
+        let branches = counts("branches-covered", "branches-valid")?;
+
+        let line_percent = match lines.and_then(Counts::percent) {
+            Some(percent) => percent,
+            None => percent("line-rate")?
+                .ok_or_else(|| "the <coverage> element has no line-rate".to_string())?,
+        };
+        // Writers without branch data still emit `branch-rate="0"`, so trust the counts first.
+        let branch_percent = match branches {
+            Some(branches) => branches.percent(),
+            None => percent("branch-rate")?,
+        };
+
+        Ok(Self {
+            format: "cobertura",
+            line_percent,
+            branch_percent,
+            lines,
+            branches: branches.filter(|b| b.total > 0),
+        })
+    }
+
+    /// `data[].totals.lines` and `data[].totals.branches`, summed over every export.
+    fn llvm_cov(report: &str) -> Result<Self, String> {
+        let json: serde_json::Value = serde_json::from_str(report).map_err(|e| e.to_string())?;
+        let exports = json
+            .get("data")
+            .and_then(serde_json::Value::as_array)
+            .ok_or_else(|| "no `data` array in llvm-cov JSON export".to_string())?;
+
+        let mut lines = Counts::default();
+        let mut branches = Counts::default();
+
+        for export in exports {
+            let totals = |kind: &str| -> Counts {
+                let summary = &export["totals"][kind];
+                Counts {
+                    covered: summary["covered"].as_i64().unwrap_or_default(),
+                    total: summary["count"].as_i64().unwrap_or_default(),
+                }
+            };
+
+            let (l, b) = (totals("lines"), totals("branches"));
+            lines.covered += l.covered;
+            lines.total += l.total;
+            branches.covered += b.covered;
+            branches.total += b.total;
+        }
+
+        Self::from_counts("llvm-cov", lines, branches)
+    }
+
+    fn into_steelval(self) -> Result<SteelVal, SteelErr> {
+        let mut map = HashMap::from([
+            ("format".to_string(), self.format.into_steelval()?),
+            (
+                "line-percent".to_string(),
+                self.line_percent.into_steelval()?,
+            ),
+            (
+                "branch-percent".to_string(),
+                self.branch_percent.into_steelval()?,
+            ),
+        ]);
+
+        for (kind, counts) in [("lines", self.lines), ("branches", self.branches)] {
+            if let Some(counts) = counts {
+                map.insert(format!("{kind}-covered"), counts.covered.into_steelval()?);
+                map.insert(format!("{kind}-total"), counts.total.into_steelval()?);
+            }
+        }
+
+        map.into_steelval()
+    }
+}
+
+/// Outcome counts for a test run.
+#[derive(Debug, Default)]
+struct TestResults {
+    format: &'static str,
+    passed: i64,
+    failed: i64,
+    ignored: i64,
+}
+
+impl TestResults {
+    fn parse(report: &str, format: &str) -> Result<Self, String> {
+        let format = match format {
+            "auto" if report.trim_start().starts_with('<') => "junit",
+            "auto" => "libtest",
+            "junit" | "libtest" => format,
+            other => {
+                return Err(format!(
+                    "unknown test report format `{other}`; expected junit, libtest or auto"
+                ));
+            }
+        };
+
+        match format {
+            "junit" => Self::junit(report),
+            _ => Self::libtest(report),
+        }
+    }
+
+    /// Every `<testcase>` element: failed with a `<failure>` or `<error>` child, ignored
+    /// with `<skipped>`, passed otherwise. Suite totals are not trusted, as writers disagree
+    /// on whether `tests` includes skipped cases.
+    fn junit(report: &str) -> Result<Self, String> {
+        let mut results = Self {
+            format: "junit",
+            ..Self::default()
+        };
+
+        let document = parse_document(report)?;
+        for case in document
+            .descendants()
+            .filter(|node| node.has_tag_name("testcase"))
+        {
+            let has_child = |name: &str| case.children().any(|child| child.has_tag_name(name));
+
+            if has_child("failure") || has_child("error") {
+                results.failed += 1;
+            } else if has_child("skipped") {
+                results.ignored += 1;
+            } else {
+                results.passed += 1;
+            }
+        }
+
+        if results.total() == 0 {
+            return Err("the JUnit report has no <testcase> elements".to_string());
+        }
+
+        Ok(results)
+    }
+
+    /// One JSON object per line; `test` events with an `ok`, `failed` or `ignored` outcome
+    /// are counted across every test binary. A `timeout` event only warns that a test has
+    /// run for a minute, and the same test still reports its outcome, so it is skipped
+    /// along with lines that are not events, such as cargo's own output captured alongside.
+    fn libtest(report: &str) -> Result<Self, String> {
+        let mut results = Self {
+            format: "libtest",
+            ..Self::default()
+        };
+        let mut suites = 0;
+
+        for line in report.lines() {
+            let Ok(event) = serde_json::from_str::<serde_json::Value>(line) else {
+                continue;
+            };
+
+            match (event["type"].as_str(), event["event"].as_str()) {
+                (Some("suite"), Some("started")) => suites += 1,
+                (Some("test"), Some("ok")) => results.passed += 1,
+                (Some("test"), Some("failed")) => results.failed += 1,
+                (Some("test"), Some("ignored")) => results.ignored += 1,
+                _ => {}
+            }
+        }
+
+        if suites == 0 {
+            return Err("no libtest JSON events found".to_string());
+        }
+
+        Ok(results)
+    }
+
+    fn total(&self) -> i64 {
+        self.passed + self.failed + self.ignored
+    }
+
+    fn into_steelval(self) -> Result<SteelVal, SteelErr> {
+        HashMap::from([
+            ("format".to_string(), self.format.into_steelval()?),
+            ("passed".to_string(), self.passed.into_steelval()?),
+            ("failed".to_string(), self.failed.into_steelval()?),
+            ("ignored".to_string(), self.ignored.into_steelval()?),
+            ("total".to_string(), self.total().into_steelval()?),
+        ])
+        .into_steelval()
+    }
+}
+
+/// The attribute `name` of `node`, if it is there, parsed as a `T`.
+fn attribute<T: FromStr>(node: roxmltree::Node, name: &str) -> Result<Option<T>, String> {
+    node.attribute(name)
+        .map(|value| {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid {name}=\"{value}\" on <{}>", node.tag_name().name()))
+        })
+        .transpose()
+}
+
+fn detect(report: &str) -> &'static str {
+    let start = report.trim_start();
+    if start.starts_with('{') {
+        "llvm-cov"
+    } else if start.starts_with('<') {
+        "cobertura"
+    } else {
+        "lcov"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coverage(report: &str) -> Coverage {
+        Coverage::parse(report, "auto").unwrap()
+    }
+
+    #[test]
+    fn sums_lcov_records() {
+        let report = "\
+TN:
+SF:src/a.rs
+LF:10
+LH:5
+BRF:4
+BRH:1
+end_of_record
+SF:src/b.rs
+LF:10
+LH:10
+end_of_record
+";
+        let coverage = coverage(report);
+        assert_eq!(coverage.format, "lcov");
+        assert_eq!(coverage.line_percent, 75.0);
+        assert_eq!(coverage.branch_percent, Some(25.0));
+        assert!(Coverage::parse("LF:ten\n", "lcov").is_err());
+        assert!(Coverage::parse("TN:\n", "lcov").is_err());
+    }
+
+    #[test]
+    fn reads_cobertura_counts() {
+        let report = r#"<?xml version="1.0" ?>
+<!DOCTYPE coverage SYSTEM "http://cobertura.sourceforge.net/xml/coverage-04.dtd">
+<!-- <coverage lines-valid="1" lines-covered="1"> -->
+<coverage
+	lines-valid='8'
+	lines-covered='2'
+	branches-valid="0" branches-covered="0"
+	line-rate="0.25" branch-rate="0" version="1.9">
+  <packages/>
+</coverage>
+"#;
+        let coverage = coverage(report);
+        assert_eq!(coverage.format, "cobertura");
+        assert_eq!(coverage.line_percent, 25.0);
+        assert_eq!(coverage.branch_percent, None);
+        assert_eq!(coverage.lines.map(|l| (l.covered, l.total)), Some((2, 8)));
+    }
+
+    #[test]
+    fn falls_back_to_cobertura_rates() {
+        let coverage = coverage(r#"<coverage line-rate="0.5" branch-rate="0.125"/>"#);
+        assert_eq!(coverage.line_percent, 50.0);
+        assert_eq!(coverage.branch_percent, Some(12.5));
+        assert!(coverage.lines.is_none());
+    }
+
+    #[test]
+    fn rejects_malformed_cobertura() {
+        for report in [
+            "<coverage>",
+            "<report line-rate=\"1\"/>",
+            "<coverage branch-rate=\"1\"/>",
+            "<coverage lines-valid=\"2.5\" lines-covered=\"1\"/>",
+        ] {
+            assert!(Coverage::parse(report, "cobertura").is_err(), "{report}");
+        }
+    }
+
+    #[test]
+    fn sums_llvm_cov_exports() {
+        let report = r#"{"data": [
+            {"totals": {"lines": {"count": 4, "covered": 3}, "branches": {"count": 0, "covered": 0}}},
+            {"totals": {"lines": {"count": 4, "covered": 1}}}
+        ]}"#;
+        let coverage = coverage(report);
+        assert_eq!(coverage.format, "llvm-cov");
+        assert_eq!(coverage.line_percent, 50.0);
+        assert_eq!(coverage.branch_percent, None);
+        assert!(Coverage::parse("{}", "llvm-cov").is_err());
+    }
+
+    #[test]
+    fn counts_junit_cases_by_their_children() {
+        let report = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites tests="5">
+  <testsuite name="unit">
+    <testcase name="compares a > b"/>
+    <testcase name="panics">
+      <failure message="assertion failed">left != right</failure>
+    </testcase>
+    <testcase name="crashes"><error type="signal"/></testcase>
+    <testcase name="slow"><skipped/></testcase>
+    <testcase name="logs">
+      <system-out><![CDATA[<failure> is only text here]]></system-out>
+      <system-err>&lt;error&gt;</system-err>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#;
+        let results = TestResults::parse(report, "auto").unwrap();
+        assert_eq!(results.format, "junit");
+        assert_eq!((results.passed, results.failed, results.ignored), (2, 2, 1));
+        assert!(TestResults::parse("<testsuites/>", "junit").is_err());
+        assert!(TestResults::parse("<testsuite><testcase>", "junit").is_err());
+    }
+
+    #[test]
+    fn counts_libtest_events() {
+        let report = r#"   Compiling badger v0.1.0
+{ "type": "suite", "event": "started", "test_count": 3 }
+{ "type": "test", "event": "started", "name": "a" }
+{ "type": "test", "name": "a", "event": "ok" }
+{ "type": "test", "name": "b", "event": "failed" }
+{ "type": "test", "name": "c", "event": "ignored" }
+{ "type": "suite", "event": "failed", "passed": 1, "failed": 1, "ignored": 1 }
+{ "type": "suite", "event": "started", "test_count": 2 }
+{ "type": "test", "name": "d", "event": "timeout" }
+{ "type": "test", "name": "e", "event": "timeout" }
+{ "type": "test", "name": "d", "event": "ok" }
+{ "type": "test", "name": "e", "event": "failed" }
+"#;
+        let results = TestResults::parse(report, "auto").unwrap();
+        assert_eq!(results.format, "libtest");
+        assert_eq!((results.passed, results.failed, results.ignored), (2, 2, 1));
+        assert!(TestResults::parse("running 0 tests\n", "libtest").is_err());
+    }
+}
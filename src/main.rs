@@ -1,19 +1,33 @@
-use steel::SteelErr;
+use clap::Parser;
 use steel::SteelVal;
-use steel::rerrs::ErrorKind;
 use steel::steel_vm::engine::Engine;
-use tracing::{info, instrument, warn};
+use tracing::{debug, info, instrument, warn};
 
-use crate::badger::{Badge, Globals};
+use crate::badger::{Badge, Commands, Globals, Project, Targets};
+use crate::cache::{Cache, Inputs, Record, Recording};
+use crate::cli::{Cli, Command, PathArgs, RenderArgs};
+use crate::entry::Entry;
 use crate::error::BadgerError;
+use crate::markdown::{BadgeLink, relative_link, replace_badge_placeholders};
+use crate::plugin_doc::{Presence, type_name};
+use crate::registry::{Origin, ProducerConfig, Registry};
 use crate::svg::{BadgerOptions, badgen};
 
 use std::fs;
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 mod badger;
+mod cache;
+mod cli;
 mod documentation;
+mod entry;
 mod error;
+mod host;
+mod markdown;
+mod params;
+mod plugin_doc;
+mod registry;
 mod steel_engine;
 mod svg;
 mod wrappers;
@@ -26,133 +40,481 @@ fn main() -> Result<(), BadgerError> {
             tracing_subscriber::EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
         )
+        .with_writer(std::io::stderr)
         .init();
 
+    let cli = Cli::parse();
+
     info!("starting armour badge generator");
-    let mut engine = steel_engine::setup()?;
 
-    let config: badger::Config = toml::from_str(include_str!("../badger.toml"))?;
+    match cli.command {
+        Command::Generate => generate(&cli.paths, !cli.no_cache),
+        Command::Check => check(&cli.paths, !cli.no_cache),
+        Command::ListProducers => list_producers(&cli.paths),
+        Command::Render(args) => render(&args),
+        Command::Clean => clean(&cli.paths),
+    }
+}
+
+/// Find the project the same way Cargo does: an explicit `--config` wins, otherwise
+/// walk up from `--root` (or the working directory) until a configuration turns up.
+fn load_project(paths: &PathArgs) -> Result<Project, BadgerError> {
+    let start = match &paths.root {
+        Some(root) => root.clone(),
+        None => std::env::current_dir()?,
+    };
+
+    match &paths.config {
+        Some(config) => {
+            let config = start.join(config);
+            let root = match &paths.root {
+                Some(root) => root.as_path(),
+                None => config.parent().unwrap_or(Path::new(".")),
+            };
+            Project::load(&config, root)
+        }
+        None => Project::discover(&start),
+    }
+}
+
+/// The markdown files to update: `--target` flags win over `targets` in the configuration.
+fn markdown_targets(paths: &PathArgs, project: &Project) -> Result<Vec<PathBuf>, BadgerError> {
+    if paths.target.is_empty() {
+        project.config.globals.targets.resolve(&project.root, false)
+    } else {
+        Targets::Many(paths.target.clone()).resolve(&project.root, true)
+    }
+}
 
-    let badges_dir = Path::new("badges");
-    fs::create_dir_all(badges_dir)?;
+fn generate(paths: &PathArgs, reuse: bool) -> Result<(), BadgerError> {
+    let project = load_project(paths)?;
+    let cache = Cache::open(&project.root, reuse)?;
+
+    let badges_dir = project.resolve(&paths.out_dir);
+    let generated = process_badges(
+        &mut Producers::new(&project),
+        &cache,
+        &project.config.badges,
+        &project.config.globals,
+        &badges_dir,
+    )?;
+
+    fs::create_dir_all(&badges_dir)?;
+
+    for badge in &generated {
+        let svg_path = badges_dir.join(badge.filename());
+        // Unchanged files are left alone, so a run that changes nothing touches nothing.
+        if fs::read_to_string(&svg_path).ok().as_ref() != Some(&badge.svg) {
+            fs::write(&svg_path, &badge.svg)?;
+            info!(path = %svg_path.display(), "wrote badge SVG");
+        }
+    }
 
-    let img_tags = process_badges(&mut engine, &config.badges, &config.globals, badges_dir)?;
+    for target in markdown_targets(paths, &project)? {
+        let markdown = fs::read_to_string(&target)?;
+        let updated =
+            replace_badge_placeholders(&markdown, &badge_links(&generated, &target, &badges_dir));
 
-    let markdown = fs::read_to_string("README.md")?;
-    let updated = replace_badge_placeholder(&markdown, &img_tags);
-    fs::write("README.md", updated)?;
+        if updated != markdown {
+            fs::write(&target, updated)?;
+            info!(path = %target.display(), "updated markdown target");
+        }
+    }
 
     Ok(())
 }
 
-/// For each badge, generate the SVG file and return the image tags in order.
-#[instrument(skip_all, fields(badge_count = badges.len()))]
-fn process_badges(
-    engine: &mut Engine,
-    badges: &[Badge],
-    globals: &Globals,
-    badges_dir: &Path,
-) -> Result<Vec<String>, BadgerError> {
-    let mut img_tags = Vec::new();
+/// Produce every badge and compare its SVG and the markdown targets against what is on
+/// disk. Nothing is written, not even the cache.
+fn check(paths: &PathArgs, reuse: bool) -> Result<(), BadgerError> {
+    let project = load_project(paths)?;
+    let cache = Cache::open(&project.root, reuse)?.read_only();
+
+    let badges_dir = project.resolve(&paths.out_dir);
+    let generated = process_badges(
+        &mut Producers::new(&project),
+        &cache,
+        &project.config.badges,
+        &project.config.globals,
+        &badges_dir,
+    )?;
+
+    let mut stale = Vec::new();
+
+    for badge in &generated {
+        let svg_path = badges_dir.join(badge.filename());
+        match fs::read_to_string(&svg_path) {
+            Ok(svg) if svg == badge.svg => {}
+            Ok(_) => stale.push(format!("outdated {}", svg_path.display())),
+            Err(_) => stale.push(format!("missing {}", svg_path.display())),
+        }
+    }
 
-    for badge in badges {
-        let raw_entry: SteelVal =
-            engine.call_function_by_name_with_args(badge.producer.entry_point(), vec![])?;
+    for target in markdown_targets(paths, &project)? {
+        let markdown = fs::read_to_string(&target)?;
+        let updated =
+            replace_badge_placeholders(&markdown, &badge_links(&generated, &target, &badges_dir));
 
-        let entry: Entry = raw_entry.try_into().map_err(BadgerError::Steel)?;
+        if updated != markdown {
+            stale.push(format!("outdated placeholder in {}", target.display()));
+        }
+    }
 
-        info!(id = %badge.id.clone().unwrap_or("NONE".to_string()), label = %entry.key, status = %entry.value, "generating badge");
+    if !stale.is_empty() {
+        return Err(BadgerError::OutOfDate(stale.join(", ")));
+    }
 
-        let svg_doc = badgen(BadgerOptions {
-            primary_color: Some(&badge.primary_color),
-            secondary_color: Some(&badge.secondary_color),
-            label: Some(entry.key.clone().trim_matches('"')),
-            status: entry.value.clone().trim_matches('"'),
-            icon: None,
-            scale: Some(globals.scale as f64),
-        })?;
+    info!("all {} badge(s) are up to date", generated.len());
+    Ok(())
+}
 
-        let filename = format!(
-            "{}.svg",
-            badge
-                .id
-                .clone()
-                .unwrap_or(badge.producer.entry_point().to_string())
-        );
-        let svg_path = badges_dir.join(&filename);
-        fs::write(&svg_path, svg_doc.to_string())?;
+/// Print every producer available to the project: the built-ins, plus any local plugins
+/// when a project can be found.
+fn list_producers(paths: &PathArgs) -> Result<(), BadgerError> {
+    let project = load_project(paths).ok();
+    let root = match &project {
+        Some(project) => project.root.clone(),
+        None => std::env::current_dir()?,
+    };
+    let default_commands = Commands::default();
+    let commands = project
+        .as_ref()
+        .map_or(&default_commands, |p| &p.config.globals.commands);
+    let mut engine = steel_engine::setup(&root, commands, &Inputs::default())?;
+    let registry = Registry::load(&mut engine, project.as_ref().map(|p| p.root.as_path()))?;
+
+    let mut stdout = std::io::stdout().lock();
+
+    for producer in registry.iter() {
+        match &producer.origin {
+            Origin::Builtin(_) => writeln!(stdout, "{}", producer.name)?,
+            Origin::Local(path) => writeln!(stdout, "{} ({})", producer.name, path.display())?,
+        }
+        if !producer.summary().is_empty() {
+            writeln!(stdout, "    {}", producer.summary())?;
+        }
+        for param in producer.params() {
+            let ty = param
+                .param_type
+                .as_ref()
+                .map_or("string".to_string(), type_name);
+            let ty = if ty.starts_with('{') {
+                ty
+            } else {
+                format!("{{{ty}}}")
+            };
+            let presence = match Presence::of(param) {
+                Presence::Defaulted(default) => format!("[opt={default}]"),
+                Presence::Optional => "[opt]".to_string(),
+                Presence::Required => String::new(),
+            };
+            writeln!(
+                stdout,
+                "    {}{presence} {ty} {}",
+                param.name,
+                param.description.as_deref().unwrap_or_default()
+            )?;
+        }
+    }
 
-        info!(path = %svg_path.display(), "wrote badge SVG");
+    Ok(())
+}
 
-        img_tags.push(format!(
-            "![{}: {}](badges/{})",
-            entry.key, entry.value, filename
-        ));
+fn render(args: &RenderArgs) -> Result<(), BadgerError> {
+    let svg_doc = badgen(BadgerOptions {
+        primary_color: Some(&args.primary_color),
+        secondary_color: Some(&args.secondary_color),
+        label: args.label.as_deref(),
+        status: &args.status,
+        icon: None,
+        title: None,
+        scale: Some(args.scale as f64),
+    })?;
+
+    match &args.output {
+        Some(path) => {
+            fs::write(path, svg_doc.to_string())?;
+            info!(path = %path.display(), "wrote badge SVG");
+        }
+        None => writeln!(std::io::stdout().lock(), "{svg_doc}")?,
     }
 
-    Ok(img_tags)
+    Ok(())
 }
 
-/// Find the single `<div badges="true">...</div>` and replace its inner content
-/// with all generated badge image links, preserving the wrapper div so users can move it.
-fn replace_badge_placeholder(markdown: &str, img_tags: &[String]) -> String {
-    let open_tag = "<div badges=\"true\">";
-    let close_tag = "</div>";
+/// Remove the SVG of every configured badge, and the output directory if that leaves it empty.
+fn clean(paths: &PathArgs) -> Result<(), BadgerError> {
+    let project = load_project(paths)?;
+    let badges_dir = project.resolve(&paths.out_dir);
+
+    for badge in &project.config.badges {
+        let svg_path = badges_dir.join(format!("{}.svg", badge_id(badge)));
+        match fs::remove_file(&svg_path) {
+            Ok(()) => info!(path = %svg_path.display(), "removed badge SVG"),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
 
-    let Some(open_start) = markdown.find(open_tag) else {
-        warn!("no <div data-badger> found in markdown");
-        return markdown.to_string();
-    };
+    if fs::read_dir(&badges_dir).is_ok_and(|mut entries| entries.next().is_none()) {
+        fs::remove_dir(&badges_dir)?;
+    }
 
-    let after_open = open_start + open_tag.len();
+    Ok(())
+}
 
-    let Some(close_offset) = markdown[after_open..].find(close_tag) else {
-        warn!("found <div data-badger> but no closing </div>");
-        return markdown.to_string();
-    };
+/// A rendered badge that has not been written to disk yet.
+struct GeneratedBadge {
+    id: String,
+    group: Option<String>,
+    entry: Entry,
+    svg: String,
+}
 
-    let close_start = after_open + close_offset;
-    let after_close = close_start + close_tag.len();
+impl GeneratedBadge {
+    fn filename(&self) -> String {
+        svg_filename(&self.id)
+    }
+}
 
-    let inner = img_tags.join("\n");
+/// The file a badge is written to, inside the badges directory.
+fn svg_filename(id: &str) -> String {
+    format!("{id}.svg")
+}
 
-    info!("updated badge placeholder with {} badge(s)", img_tags.len());
+/// The badge's configured id, falling back to its producer's name.
+fn badge_id(badge: &Badge) -> String {
+    badge
+        .id
+        .clone()
+        .unwrap_or_else(|| badge.producer.name.clone())
+}
 
-    format!(
-        "{}{}\n{}\n{}{}",
-        &markdown[..open_start],
-        open_tag,
-        inner,
-        close_tag,
-        &markdown[after_close..],
-    )
+/// The engine and the producers loaded into it, set up on the first cache miss so that a
+/// run answered entirely from the cache never compiles a plugin.
+struct Producers<'a> {
+    project: &'a Project,
+    /// What the running producer reads through the host functions.
+    inputs: Inputs,
+    loaded: Option<(Engine, Registry)>,
 }
 
-#[derive(Debug)]
-struct Entry {
-    key: String,
-    value: String,
-    icon: Option<String>,
+impl<'a> Producers<'a> {
+    fn new(project: &'a Project) -> Self {
+        Self {
+            project,
+            inputs: Inputs::default(),
+            loaded: None,
+        }
+    }
+
+    /// Run `producer`, returning what it returned and what it read.
+    fn run(&mut self, producer: &ProducerConfig) -> Result<(SteelVal, Recording), BadgerError> {
+        if self.loaded.is_none() {
+            let root = &self.project.root;
+            let mut engine =
+                steel_engine::setup(root, &self.project.config.globals.commands, &self.inputs)?;
+            let registry = Registry::load(&mut engine, Some(root))?;
+            self.loaded = Some((engine, registry));
+        }
+        let Some((engine, registry)) = &mut self.loaded else {
+            unreachable!("the engine was loaded above");
+        };
+
+        let args = registry.resolve(producer)?;
+        self.inputs.take();
+        let value = engine
+            .call_function_by_name_with_args(&producer.name, args)
+            .map_err(|e| {
+                let file = registry.get(&producer.name).map(|p| p.file().into_owned());
+                steel_engine::plugin_error(engine, file.as_deref().unwrap_or_default(), e)
+            })?;
+
+        Ok((value, self.inputs.take()))
+    }
 }
 
-impl TryFrom<SteelVal> for Entry {
-    type Error = SteelErr;
+/// For each badge, run its producer and render the SVG, in order. A producer is only run,
+/// and an SVG only drawn, when `cache` has no current result for it; an SVG that need not
+/// be drawn is read back from `badges_dir`, unless it changed since it was written.
+#[instrument(skip_all, fields(badge_count = badges.len()))]
+fn process_badges(
+    producers: &mut Producers,
+    cache: &Cache,
+    badges: &[Badge],
+    globals: &Globals,
+    badges_dir: &Path,
+) -> Result<Vec<GeneratedBadge>, BadgerError> {
+    let mut generated = Vec::new();
 
-    fn try_from(val: SteelVal) -> std::result::Result<Self, Self::Error> {
-        let items: Vec<SteelVal> = match val {
-            SteelVal::ListV(list) => list.into_iter().collect(),
-            _ => {
-                return Err(SteelErr::new(
-                    ErrorKind::TypeMismatch,
-                    "expected a list".to_string(),
-                ));
+    for badge in badges {
+        let id = badge_id(badge);
+        let key = cache.producer_key(&badge.producer);
+        let previous = cache.load(&id);
+
+        let (entry, recording) = match previous.as_ref().and_then(|record| record.entry(&key)) {
+            Some(entry) => {
+                debug!(id, "producer inputs unchanged, reusing its entry");
+                (entry.clone(), None)
+            }
+            None => {
+                let (raw_entry, recording) = producers.run(&badge.producer)?;
+                let entry = Entry::from_producer(&badge.producer.name, raw_entry)?;
+                (entry, Some(recording))
             }
         };
+        let configured = entry.clone().or_configured(badge);
 
-        Ok(Self {
-            key: items[0].to_string(),
-            value: items[1].to_string(),
-            icon: items.get(2).map(|v| v.to_string()),
+        info!(id = %badge.id.clone().unwrap_or("NONE".to_string()), label = %configured.label, status = %configured.message, "generating badge");
+
+        let style = match &configured.style {
+            Some(style) => style.parse()?,
+            None => badge.style,
+        };
+        let render_key = cache::render_key(&configured, style, globals.scale);
+        let cached_svg = previous.as_ref().and_then(|record| {
+            fs::read_to_string(badges_dir.join(svg_filename(&id)))
+                .ok()
+                .filter(|svg| record.drew(&render_key, svg))
+        });
+        let drawn = cached_svg.is_none();
+        let svg = match cached_svg {
+            Some(svg) => svg,
+            None => svg::render(
+                style,
+                BadgerOptions {
+                    primary_color: configured.label_color.as_deref(),
+                    secondary_color: configured.color.as_deref(),
+                    label: Some(&configured.label),
+                    status: &configured.message,
+                    icon: configured.icon.as_deref(),
+                    title: configured.title.as_deref(),
+                    scale: Some(globals.scale as f64),
+                },
+            )?
+            .to_string(),
+        };
+
+        if recording.is_some() || drawn {
+            // A reused entry keeps the inputs it was recorded with.
+            let inputs = match recording {
+                Some(recording) => (!recording.volatile).then_some(recording.inputs),
+                None => previous.and_then(Record::into_inputs),
+            };
+            cache.store(&id, &Record::new(key, inputs, entry, render_key, &svg));
+        }
+
+        generated.push(GeneratedBadge {
+            id,
+            group: badge.group.clone(),
+            entry: configured,
+            svg,
+        });
+    }
+
+    Ok(generated)
+}
+
+/// The markdown image tag for each badge, linking from `target`'s directory into `badges_dir`.
+fn badge_links<'a>(
+    generated: &'a [GeneratedBadge],
+    target: &Path,
+    badges_dir: &Path,
+) -> Vec<BadgeLink<'a>> {
+    let target_dir = target.parent().unwrap_or(Path::new(""));
+
+    generated
+        .iter()
+        .map(|badge| {
+            let entry = &badge.entry;
+            let path = relative_link(target_dir, &badges_dir.join(badge.filename()));
+            let image = match &entry.title {
+                Some(title) => format!(
+                    "![{}: {}]({path} \"{}\")",
+                    entry.label,
+                    entry.message,
+                    title.replace('"', "\\\"")
+                ),
+                None => format!("![{}: {}]({path})", entry.label, entry.message),
+            };
+
+            BadgeLink {
+                id: &badge.id,
+                group: badge.group.as_deref(),
+                tag: match &entry.link {
+                    Some(link) => format!("[{image}]({link})"),
+                    None => image,
+                },
+            }
         })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(root: &Path, args: &[&str]) -> Result<(), BadgerError> {
+        let root = root.to_string_lossy();
+        let cli = Cli::try_parse_from(["badger", "--root", &root].iter().chain(args)).unwrap();
+        match cli.command {
+            Command::Generate => generate(&cli.paths, !cli.no_cache),
+            Command::Check => check(&cli.paths, !cli.no_cache),
+            Command::Clean => clean(&cli.paths),
+            other => panic!("unexpected command {other:?}"),
+        }
+    }
+
+    #[test]
+    fn generates_checks_and_cleans_a_project() {
+        let root = std::env::temp_dir().join(format!("badger-cli-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        fs::write(
+            root.join("Cargo.toml"),
+            "[package]\nname = \"demo\"\nversion = \"0.3.0\"\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("badger.toml"),
+            "[globals]\nscale = 1\ntargets = \"README.md\"\n\n[[badge]]\nid = \"version\"\n\
+             primary_color = \"blue\"\nsecondary_color = \"purple\"\n\
+             producer = { name = \"crate-version\" }\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("README.md"),
+            "# demo\n<div badges=\"true\"></div>\n",
+        )
+        .unwrap();
+        let svg = root.join("badges/version.svg");
+
+        let missing = run(&root, &["check"]);
+        run(&root, &["generate"]).unwrap();
+        let readme = fs::read_to_string(root.join("README.md")).unwrap();
+        let fresh = run(&root, &["check"]);
+        fs::write(&svg, "<svg/>").unwrap();
+        let edited = run(&root, &["check"]);
+        let uncached = run(&root, &["--no-cache", "check"]);
+        run(&root, &["generate"]).unwrap();
+        let repaired = run(&root, &["check"]);
+        let unmatched = run(&root, &["--target", "CHANGELOG.md", "check"]);
+        run(&root, &["clean"]).unwrap();
+        let cleaned = !root.join("badges").exists();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(matches!(missing, Err(BadgerError::OutOfDate(_))));
+        assert!(readme.contains("badges/version.svg"), "{readme}");
+        assert!(fresh.is_ok());
+        for stale in [edited, uncached] {
+            let error = stale.unwrap_err().to_string();
+            assert!(
+                error.contains("outdated") && error.contains("version.svg"),
+                "{error}"
+            );
+        }
+        assert!(repaired.is_ok());
+        assert!(unmatched.is_err());
+        assert!(cleaned);
     }
 }
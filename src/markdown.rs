@@ -0,0 +1,317 @@
+use std::path::{Component, Path};
+
+use tracing::{info, warn};
+
+const OPEN_TAG: &str = "<div";
+const CLOSE_TAG: &str = "</div>";
+
+/// A badge ready to be spliced into markdown.
+#[derive(Debug)]
+pub(crate) struct BadgeLink<'a> {
+    /// The badge id, matched against `badges="id,id"` placeholders.
+    pub(crate) id: &'a str,
+    /// The badge group, matched against `group="name"` placeholders.
+    pub(crate) group: Option<&'a str>,
+    /// The rendered `![label: status](path)` image tag.
+    pub(crate) tag: String,
+}
+
+/// Which badges a placeholder asks for.
+#[derive(Debug)]
+enum Selector {
+    /// `<div badges="true">` — every badge, in configuration order.
+    All,
+    /// `<div badges="true" group="ci">` — every badge in the group, in configuration order.
+    Group(String),
+    /// `<div badges="edition,license">` — exactly these ids, in the listed order.
+    Ids(Vec<String>),
+}
+
+impl Selector {
+    /// Parse the attributes of an opening `<div …>` tag, returning `None` for ordinary divs.
+    fn from_attributes(attributes: &str) -> Option<Self> {
+        let attributes = parse_attributes(attributes);
+        let value = |name: &str| {
+            attributes
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| *value)
+        };
+
+        let badges = value("badges")?;
+
+        // `badges="false"` switches a placeholder off, whatever else it asks for.
+        if badges == "false" {
+            return None;
+        }
+        if let Some(group) = value("group") {
+            return Some(Self::Group(group.to_string()));
+        }
+
+        match badges {
+            "true" => Some(Self::All),
+            ids => Some(Self::Ids(
+                ids.split(',')
+                    .map(str::trim)
+                    .filter(|id| !id.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+            )),
+        }
+    }
+
+    fn select<'l, 'a>(&self, links: &'l [BadgeLink<'a>]) -> Vec<&'l BadgeLink<'a>> {
+        match self {
+            Self::All => links.iter().collect(),
+            Self::Group(group) => links
+                .iter()
+                .filter(|l| l.group == Some(group.as_str()))
+                .collect(),
+            Self::Ids(ids) => ids
+                .iter()
+                .filter_map(|id| {
+                    let link = links.iter().find(|l| l.id == id);
+                    if link.is_none() {
+                        warn!(id, "placeholder names a badge that is not configured");
+                    }
+                    link
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Split `key="value" key='value'` pairs out of a tag body. Bare attributes are ignored.
+fn parse_attributes(input: &str) -> Vec<(&str, &str)> {
+    let mut attributes = Vec::new();
+    let mut rest = input;
+
+    while let Some(eq) = rest.find('=') {
+        let key = rest[..eq].split_whitespace().last().unwrap_or_default();
+        let after = rest[eq + 1..].trim_start();
+
+        let Some(quote) = after.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            rest = after;
+            continue;
+        };
+        let Some(end) = after[1..].find(quote) else {
+            break;
+        };
+
+        attributes.push((key, &after[1..end + 1]));
+        rest = &after[end + 2..];
+    }
+
+    attributes
+}
+
+/// The offset of the next `<div` tag in `html`, skipping other tags that start the same
+/// way, such as `<divider>`.
+fn find_div(html: &str) -> Option<usize> {
+    html.match_indices(OPEN_TAG)
+        .map(|(i, _)| i)
+        .find(|i| html[i + OPEN_TAG.len()..].starts_with(|c: char| c.is_whitespace() || c == '>'))
+}
+
+/// The offset in `content` of the `</div>` closing the div that `content` is the inside of,
+/// past any divs nested within it.
+fn matching_close(content: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut offset = 0;
+
+    loop {
+        let rest = &content[offset..];
+        let close = rest.find(CLOSE_TAG)?;
+        match find_div(&rest[..close]) {
+            Some(open) => {
+                depth += 1;
+                offset += open + OPEN_TAG.len();
+            }
+            None if depth == 0 => return Some(offset + close),
+            None => {
+                depth -= 1;
+                offset += close + CLOSE_TAG.len();
+            }
+        }
+    }
+}
+
+/// Replace the inner content of every `<div badges=…>…</div>` placeholder with the badges
+/// it selects, preserving the wrapper divs so users can move them around. Divs nested
+/// inside a placeholder are replaced along with the rest of its content.
+pub(crate) fn replace_badge_placeholders(markdown: &str, links: &[BadgeLink]) -> String {
+    let mut output = String::with_capacity(markdown.len());
+    let mut rest = markdown;
+    let mut replaced = 0;
+
+    while let Some(open_start) = rest.find(OPEN_TAG) {
+        let Some(open_len) = rest[open_start..].find('>').map(|end| end + 1) else {
+            break;
+        };
+        let open_tag = &rest[open_start..open_start + open_len];
+        let after_open = open_start + open_len;
+
+        let attributes = &open_tag[OPEN_TAG.len()..open_len - 1];
+        let is_div = attributes.is_empty() || attributes.starts_with(char::is_whitespace);
+
+        let Some(selector) = Selector::from_attributes(attributes).filter(|_| is_div) else {
+            output.push_str(&rest[..after_open]);
+            rest = &rest[after_open..];
+            continue;
+        };
+
+        let Some(close_offset) = matching_close(&rest[after_open..]) else {
+            warn!(
+                tag = open_tag,
+                "found badge placeholder but no closing </div>"
+            );
+            break;
+        };
+
+        let selected = selector.select(links);
+        let inner = selected
+            .iter()
+            .map(|link| link.tag.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        output.push_str(&rest[..open_start]);
+        output.push_str(open_tag);
+        output.push('\n');
+        output.push_str(&inner);
+        output.push('\n');
+        output.push_str(CLOSE_TAG);
+
+        info!(
+            tag = open_tag,
+            "updated badge placeholder with {} badge(s)",
+            selected.len()
+        );
+
+        replaced += 1;
+        rest = &rest[after_open + close_offset + CLOSE_TAG.len()..];
+    }
+
+    output.push_str(rest);
+
+    if replaced == 0 {
+        warn!("no <div badges=…> placeholder found in markdown");
+    }
+
+    output
+}
+
+/// A forward-slashed relative link from `from_dir` to `to`. Both paths must share a root,
+/// which holds for everything resolved through [`crate::badger::Project::resolve`].
+pub(crate) fn relative_link(from_dir: &Path, to: &Path) -> String {
+    fn normalize(path: &Path) -> Vec<String> {
+        let mut parts: Vec<String> = Vec::new();
+        for component in path.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    parts.pop();
+                }
+                other => parts.push(other.as_os_str().to_string_lossy().into_owned()),
+            }
+        }
+        parts
+    }
+
+    let from = normalize(from_dir);
+    let to = normalize(to);
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    std::iter::repeat_n("..".to_string(), from.len() - common)
+        .chain(to[common..].iter().cloned())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn links() -> Vec<BadgeLink<'static>> {
+        [
+            ("edition", None),
+            ("tests", Some("ci")),
+            ("coverage", Some("ci")),
+        ]
+        .into_iter()
+        .map(|(id, group)| BadgeLink {
+            id,
+            group,
+            tag: format!("![{id}]"),
+        })
+        .collect()
+    }
+
+    #[test]
+    fn fills_every_kind_of_placeholder() {
+        let markdown = r#"# Title
+<div badges="true">old</div>
+<div badges="true" group='ci'>
+</div>
+<div badges="coverage, edition, missing"></div>
+"#;
+        let expected = r#"# Title
+<div badges="true">
+![edition]
+![tests]
+![coverage]
+</div>
+<div badges="true" group='ci'>
+![tests]
+![coverage]
+</div>
+<div badges="coverage, edition, missing">
+![coverage]
+![edition]
+</div>
+"#;
+        assert_eq!(replace_badge_placeholders(markdown, &links()), expected);
+    }
+
+    #[test]
+    fn leaves_other_tags_alone() {
+        let markdown = r#"<div badges="false" group="ci">keep</div>
+<divider badges="true">keep</divider>
+<div class="badges">keep</div>
+"#;
+        assert_eq!(replace_badge_placeholders(markdown, &links()), markdown);
+    }
+
+    #[test]
+    fn replaces_nested_divs_with_the_placeholder() {
+        let markdown = r#"<div badges="edition">
+<div align="center"><div>old</div></div>
+</div>
+<div>after</div>
+"#;
+        let expected = r#"<div badges="edition">
+![edition]
+</div>
+<div>after</div>
+"#;
+        assert_eq!(replace_badge_placeholders(markdown, &links()), expected);
+    }
+
+    #[test]
+    fn keeps_an_unclosed_placeholder() {
+        let markdown = "<div badges=\"true\">\n<div></div>\n";
+        assert_eq!(replace_badge_placeholders(markdown, &links()), markdown);
+    }
+
+    #[test]
+    fn links_relative_to_the_target() {
+        assert_eq!(
+            relative_link(Path::new("docs"), Path::new("badges/a.svg")),
+            "../badges/a.svg"
+        );
+        assert_eq!(
+            relative_link(Path::new(""), Path::new("./badges/a.svg")),
+            "badges/a.svg"
+        );
+    }
+}
@@ -0,0 +1,403 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use serde::Deserialize;
+use steel::SteelErr;
+use steel::SteelVal;
+use steel::rerrs::ErrorKind;
+use steel::rvals::IntoSteelVal;
+
+use crate::documentation::TypeExpr;
+use crate::plugin_doc::{canonical_type, type_name};
+use crate::wrappers::toml::toml_value_to_steelval;
+
+/// Converts a typed producer parameter, as declared by a plugin's `@param {…}` annotation,
+/// into the argument its Steel entry point receives.
+pub(crate) trait IntoProducerArg {
+    fn into_producer_arg(self) -> Result<SteelVal, SteelErr>;
+}
+
+macro_rules! via_into_steelval {
+    ($($ty:ty),*) => {
+        $(
+            impl IntoProducerArg for $ty {
+                fn into_producer_arg(self) -> Result<SteelVal, SteelErr> {
+                    self.into_steelval()
+                }
+            }
+        )*
+    };
+}
+
+via_into_steelval!(SteelVal, String, i64, f64, bool);
+
+/// A missing optional parameter is passed as `#f`, the Scheme convention for "nothing".
+impl<T: IntoProducerArg> IntoProducerArg for Option<T> {
+    fn into_producer_arg(self) -> Result<SteelVal, SteelErr> {
+        match self {
+            Some(value) => value.into_producer_arg(),
+            None => Ok(SteelVal::BoolV(false)),
+        }
+    }
+}
+
+impl<T: IntoProducerArg> IntoProducerArg for Vec<T> {
+    fn into_producer_arg(self) -> Result<SteelVal, SteelErr> {
+        let items = self
+            .into_iter()
+            .map(IntoProducerArg::into_producer_arg)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(SteelVal::ListV(items.into()))
+    }
+}
+
+impl<K: IntoProducerArg, V: IntoProducerArg> IntoProducerArg for HashMap<K, V> {
+    fn into_producer_arg(self) -> Result<SteelVal, SteelErr> {
+        self.into_iter()
+            .map(|(k, v)| Ok((k.into_producer_arg()?, v.into_producer_arg()?)))
+            .collect::<Result<HashMap<_, _>, SteelErr>>()?
+            .into_steelval()
+    }
+}
+
+macro_rules! tuple_into_list {
+    ($($name:ident),*) => {
+        impl<$($name: IntoProducerArg),*> IntoProducerArg for ($($name,)*) {
+            #[allow(non_snake_case)]
+            fn into_producer_arg(self) -> Result<SteelVal, SteelErr> {
+                let ($($name,)*) = self;
+                Ok(SteelVal::ListV(vec![$($name.into_producer_arg()?),*].into()))
+            }
+        }
+    };
+}
+
+tuple_into_list!(A, B);
+tuple_into_list!(A, B, C);
+tuple_into_list!(A, B, C, D);
+
+/// Unions of several types are kept as the raw TOML value they were written as.
+impl IntoProducerArg for toml::Value {
+    fn into_producer_arg(self) -> Result<SteelVal, SteelErr> {
+        toml_value_to_steelval(self).map_err(|e| SteelErr::new(ErrorKind::ConversionError, e))
+    }
+}
+
+/// `{path}` — passed to the producer as a string.
+impl IntoProducerArg for PathBuf {
+    fn into_producer_arg(self) -> Result<SteelVal, SteelErr> {
+        self.to_string_lossy().into_owned().into_steelval()
+    }
+}
+
+/// `{glob}` — a pattern checked to be a valid glob when the configuration is loaded.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Glob(String);
+
+impl FromStr for Glob {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        glob::Pattern::new(s)
+            .map(|_| Self(s.to_string()))
+            .map_err(|e| format!("invalid glob {s:?}: {e}"))
+    }
+}
+
+/// `{color}` — any CSS color, checked when the configuration is loaded.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Color(String);
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        color::parse_color(s)
+            .map(|_| Self(s.to_string()))
+            .map_err(|e| format!("invalid color {s:?}: {e}"))
+    }
+}
+
+/// `{duration}` — written as `90s`, `15m`, `1h30m`, `2d` or `1w`, and passed to the
+/// producer as a whole number of seconds. A bare integer is taken as seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "toml::Value")]
+pub struct Duration(i64);
+
+impl Duration {
+    pub(crate) const fn from_secs(seconds: i64) -> Self {
+        Self(seconds)
+    }
+
+    /// A configured number of seconds, which may not be negative.
+    fn try_from_secs(seconds: i64) -> Result<Self, String> {
+        if seconds < 0 {
+            Err(format!("duration may not be negative, found {seconds}"))
+        } else {
+            Ok(Self(seconds))
+        }
+    }
+
+    /// As a [`std::time::Duration`].
+    pub(crate) fn to_std(self) -> std::time::Duration {
+        std::time::Duration::from_secs(u64::try_from(self.0).unwrap_or(0))
+    }
+}
+
+impl FromStr for Duration {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid duration {s:?}, expected e.g. `90s`, `1h30m` or `2d`");
+
+        let mut rest = s.trim();
+        if rest.is_empty() {
+            return Err(invalid());
+        }
+        if let Ok(seconds) = rest.parse() {
+            return Self::try_from_secs(seconds);
+        }
+
+        let mut total: i64 = 0;
+
+        while !rest.is_empty() {
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .ok_or_else(invalid)?;
+            let amount: i64 = rest[..digits].parse().map_err(|_| invalid())?;
+            let unit = match rest[digits..].chars().next() {
+                Some('s') => 1,
+                Some('m') => 60,
+                Some('h') => 60 * 60,
+                Some('d') => 24 * 60 * 60,
+                Some('w') => 7 * 24 * 60 * 60,
+                _ => return Err(invalid()),
+            };
+
+            total = amount
+                .checked_mul(unit)
+                .and_then(|seconds| total.checked_add(seconds))
+                .ok_or_else(invalid)?;
+            rest = &rest[digits + 1..];
+        }
+
+        Ok(Self(total))
+    }
+}
+
+macro_rules! try_from_string {
+    ($($ty:ty),*) => {
+        $(
+            impl TryFrom<String> for $ty {
+                type Error = String;
+
+                fn try_from(s: String) -> Result<Self, Self::Error> {
+                    s.parse()
+                }
+            }
+
+            impl fmt::Display for $ty {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    write!(f, "{}", self.0)
+                }
+            }
+        )*
+    };
+}
+
+try_from_string!(Glob, Color, Duration);
+
+impl TryFrom<toml::Value> for Duration {
+    type Error = String;
+
+    fn try_from(value: toml::Value) -> Result<Self, Self::Error> {
+        match value {
+            toml::Value::Integer(seconds) => Self::try_from_secs(seconds),
+            toml::Value::String(s) => s.parse(),
+            other => Err(format!("expected a duration, found {}", other.type_str())),
+        }
+    }
+}
+
+impl IntoProducerArg for Glob {
+    fn into_producer_arg(self) -> Result<SteelVal, SteelErr> {
+        self.0.into_steelval()
+    }
+}
+
+impl IntoProducerArg for Color {
+    fn into_producer_arg(self) -> Result<SteelVal, SteelErr> {
+        self.0.into_steelval()
+    }
+}
+
+impl IntoProducerArg for Duration {
+    fn into_producer_arg(self) -> Result<SteelVal, SteelErr> {
+        self.0.into_steelval()
+    }
+}
+
+/// Convert a configured value for a runtime-loaded plugin, checking it against the type the
+/// plugin declared. This mirrors what the generated `Producer` enum does for built-ins.
+/// Untyped parameters are strings.
+pub(crate) fn toml_to_arg(ty: Option<&TypeExpr>, value: toml::Value) -> Result<SteelVal, String> {
+    let Some(ty) = ty else {
+        return toml_to_arg(Some(&TypeExpr::Named("string".to_string())), value);
+    };
+
+    let mismatch =
+        |value: &toml::Value| format!("expected `{}`, found {}", type_name(ty), value.type_str());
+    let steel = |result: Result<SteelVal, SteelErr>| result.map_err(|e| e.to_string());
+
+    match (ty, value) {
+        (TypeExpr::Named(n), value) => match (canonical_type(n), value) {
+            (Some("string" | "path"), toml::Value::String(s)) => steel(s.into_steelval()),
+            (Some("int"), toml::Value::Integer(i)) => steel(i.into_steelval()),
+            (Some("float"), toml::Value::Float(f)) => steel(f.into_steelval()),
+            (Some("float"), toml::Value::Integer(i)) => steel((i as f64).into_steelval()),
+            (Some("bool"), toml::Value::Boolean(b)) => steel(b.into_steelval()),
+            (Some("glob"), toml::Value::String(s)) => steel(s.parse::<Glob>()?.into_producer_arg()),
+            (Some("color"), toml::Value::String(s)) => {
+                steel(s.parse::<Color>()?.into_producer_arg())
+            }
+            (Some("duration"), value) => steel(Duration::try_from(value)?.into_producer_arg()),
+            (Some("any"), value) => toml_value_to_steelval(value),
+            (_, value) => Err(mismatch(&value)),
+        },
+        (TypeExpr::Nullable(inner), value) => toml_to_arg(Some(inner), value),
+        (TypeExpr::Generic { name, args }, value)
+            if name.eq_ignore_ascii_case("option") && args.len() == 1 =>
+        {
+            toml_to_arg(Some(&args[0]), value)
+        }
+        (TypeExpr::Union(types), value) => types
+            .iter()
+            .filter(|t| !matches!(t, TypeExpr::Named(n) if n == "nil"))
+            .find_map(|t| toml_to_arg(Some(t), value.clone()).ok())
+            .ok_or_else(|| mismatch(&value)),
+        (TypeExpr::Array(inner), toml::Value::Array(items)) => list_arg(inner, items),
+        (TypeExpr::Generic { name, args }, toml::Value::Array(items))
+            if matches!(name.to_lowercase().as_str(), "list" | "array") && args.len() == 1 =>
+        {
+            list_arg(&args[0], items)
+        }
+        (TypeExpr::Tuple(types), toml::Value::Array(items)) if types.len() == items.len() => {
+            let items = types
+                .iter()
+                .zip(items)
+                .map(|(t, v)| toml_to_arg(Some(t), v))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(SteelVal::ListV(items.into()))
+        }
+        (TypeExpr::Map { value: inner, .. }, toml::Value::Table(table)) => map_arg(inner, table),
+        (TypeExpr::Generic { name, args }, toml::Value::Table(table))
+            if name.eq_ignore_ascii_case("map") && args.len() == 2 =>
+        {
+            map_arg(&args[1], table)
+        }
+        (TypeExpr::Struct(fields), toml::Value::Table(mut table)) => {
+            let mut map = HashMap::new();
+            for (field, t) in fields {
+                let value = table
+                    .remove(field)
+                    .ok_or_else(|| format!("missing field `{field}`"))?;
+                map.insert(field.clone(), toml_to_arg(Some(t), value)?);
+            }
+            steel(map.into_steelval())
+        }
+        (_, value) => Err(mismatch(&value)),
+    }
+}
+
+/// Convert an `[opt=value]` default, which is written as bare text in the doc comment.
+pub(crate) fn default_to_arg(ty: Option<&TypeExpr>, default: &str) -> Result<SteelVal, String> {
+    let value = match ty {
+        Some(TypeExpr::Named(n)) => match canonical_type(n) {
+            Some("int") => default.parse().map(toml::Value::Integer).ok(),
+            Some("float") => default.parse().map(toml::Value::Float).ok(),
+            Some("bool") => default.parse().map(toml::Value::Boolean).ok(),
+            _ => None,
+        },
+        _ => None,
+    }
+    .unwrap_or_else(|| toml::Value::String(default.to_string()));
+
+    toml_to_arg(ty, value)
+}
+
+fn list_arg(inner: &TypeExpr, items: Vec<toml::Value>) -> Result<SteelVal, String> {
+    let items = items
+        .into_iter()
+        .map(|v| toml_to_arg(Some(inner), v))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(SteelVal::ListV(items.into()))
+}
+
+fn map_arg(inner: &TypeExpr, table: toml::Table) -> Result<SteelVal, String> {
+    table
+        .into_iter()
+        .map(|(k, v)| Ok((k, toml_to_arg(Some(inner), v)?)))
+        .collect::<Result<HashMap<_, _>, String>>()?
+        .into_steelval()
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seconds(s: &str) -> Result<i64, String> {
+        s.parse::<Duration>().map(|d| d.0)
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(seconds("90"), Ok(90));
+        assert_eq!(seconds("90s"), Ok(90));
+        assert_eq!(seconds("15m"), Ok(15 * 60));
+        assert_eq!(seconds("1h30m"), Ok(90 * 60));
+        assert_eq!(seconds("2d"), Ok(2 * 24 * 60 * 60));
+        assert_eq!(seconds("1w"), Ok(7 * 24 * 60 * 60));
+        assert_eq!(seconds(" 0s "), Ok(0));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        for s in [
+            "",
+            "  ",
+            "-5",
+            "-5m",
+            "h",
+            "5x",
+            "1h-30m",
+            "99999999999999999w",
+        ] {
+            assert!(seconds(s).is_err(), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn takes_integers_as_seconds() {
+        assert_eq!(
+            Duration::try_from(toml::Value::Integer(30)),
+            Ok(Duration(30))
+        );
+        assert!(Duration::try_from(toml::Value::Integer(-30)).is_err());
+        assert!(Duration::try_from(toml::Value::Float(1.5)).is_err());
+    }
+
+    #[test]
+    fn converts_defaults_to_arguments() {
+        let ty = TypeExpr::Named("duration".to_string());
+        assert!(matches!(
+            default_to_arg(Some(&ty), "2m"),
+            Ok(SteelVal::IntV(120))
+        ));
+        assert!(default_to_arg(Some(&ty), "").is_err());
+    }
+}
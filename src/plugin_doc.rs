@@ -0,0 +1,698 @@
+//! Plugin loading, doc-comment parsing and parameter typing shared by `build.rs`, which
+//! generates the built-in `Producer` enum, and the runtime producer registry.
+
+use std::path::PathBuf;
+
+use chumsky::prelude::*;
+use steel::SteelErr;
+use steel::compiler::passes::analysis::query_top_level_define;
+use steel::parser::ast::ExprKind;
+use steel::parser::parser::Parser as SteelParser;
+use steel::rerrs::ErrorKind;
+use steel::steel_vm::engine::Engine;
+
+use crate::documentation::*;
+use crate::params::{Color, Duration, Glob};
+
+/// Bring a plugin's entry point into the top level from the Steel module `module`. Each
+/// plugin is its own module, so the helpers it defines stay private to it; the plugin must
+/// `(provide …)` the function named after its file.
+pub(crate) fn require_entry_point(
+    engine: &mut Engine,
+    module: &str,
+    name: &str,
+    source: &str,
+) -> Result<(), SteelErr> {
+    // `only-in` quietly binds nothing for a name the module does not provide.
+    if !provides(source, name) {
+        return Err(SteelErr::new(
+            ErrorKind::Generic,
+            format!("plugin must `(provide {name})`, its entry point"),
+        ));
+    }
+
+    engine
+        .run(format!("(require (only-in {module:?} {name}))"))
+        .map(|_| ())
+}
+
+/// Whether `source` has a top-level `(provide …)` naming `name`.
+pub(crate) fn provides(source: &str, name: &str) -> bool {
+    SteelParser::parse(source).is_ok_and(|exprs| {
+        exprs
+            .iter()
+            .filter_map(ExprKind::list)
+            .filter(|l| l.first_ident().is_some_and(|i| i.resolve() == "provide"))
+            .flat_map(|l| &l.args[1..])
+            .any(|e| e.atom_identifier().is_some_and(|i| i.resolve() == name))
+    })
+}
+
+/// The text between the first and last `"` of `input`, if it has both.
+pub(crate) fn extract_quoted_string(input: &str) -> Option<&str> {
+    let start = input.find('"')? + 1;
+    let end = input[start..].rfind('"')? + start;
+    Some(&input[start..end])
+}
+
+/// Expand `source` and parse the `;;@doc` comment of its `name` definition, if it has one.
+/// `path` is where the source lives on disk, for sources that have one.
+pub(crate) fn extract_doc(
+    engine: &mut Engine,
+    name: &str,
+    source: &str,
+    path: Option<PathBuf>,
+) -> Result<Option<Item>, String> {
+    let ast = engine
+        .emit_expanded_ast_without_optimizations(source, path)
+        .map_err(|e| e.to_string())?;
+
+    let Some(node) = query_top_level_define(&ast, format!("{name}__doc__")) else {
+        return Ok(None);
+    };
+
+    let node = node.to_string();
+    let text = extract_quoted_string(&node)
+        .ok_or_else(|| format!("the doc comment is not a string: {node}"))?;
+    parser()
+        .parse(text)
+        .into_result()
+        .map(Some)
+        .map_err(|errors| {
+            errors
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+                .join("; ")
+        })
+}
+
+/// The 1-based line of the `@param` tag declaring `param`, for error messages.
+pub(crate) fn param_line(source: &str, param: &str) -> usize {
+    source
+        .lines()
+        .position(|line| {
+            line.contains("@param")
+                && line
+                    .split(|c: char| c.is_whitespace() || c == ']')
+                    .any(|w| w == param)
+        })
+        .map_or(1, |index| index + 1)
+}
+
+pub(crate) fn parser<'a>() -> impl Parser<'a, &'a str, Item, extra::Err<Rich<'a, char>>> {
+    let ident = text::ascii::ident().map(|s: &str| s.to_string());
+
+    // rest of line (trimmed), stops before \n
+    let rest_of_line = any()
+        .filter(|c: &char| *c != '\n')
+        .repeated()
+        .collect::<String>()
+        .map(|s| s.trim().to_string());
+
+    // LDoc type expressions:
+    //   T, Container(T, …), ?T, ?|T1|T2, {T, ...}, {[K]=V}, {A=T1, B=T2}, {T1, T2}
+    // A braced group holding a single type is that type, so `{string}` is just `string`.
+    let type_expr = recursive(|ty| {
+        let item = ty.clone().padded();
+        let comma = just(',').padded();
+
+        let generic = ident
+            .then(
+                item.clone()
+                    .separated_by(just(','))
+                    .at_least(1)
+                    .collect::<Vec<_>>()
+                    .delimited_by(just('('), just(')')),
+            )
+            .map(|(name, args)| TypeExpr::Generic { name, args });
+
+        let map = item
+            .clone()
+            .delimited_by(just('['), just(']'))
+            .then_ignore(just('=').padded())
+            .then(item.clone())
+            .then_ignore(comma.then(just("...")).or_not())
+            .map(|(key, value)| TypeExpr::Map {
+                key: Box::new(key),
+                value: Box::new(value),
+            });
+
+        let array = item
+            .clone()
+            .then_ignore(comma)
+            .then_ignore(just("..."))
+            .map(|t| TypeExpr::Array(Box::new(t)));
+
+        let record = ident
+            .padded()
+            .then_ignore(just('='))
+            .then(item.clone())
+            .separated_by(just(','))
+            .at_least(1)
+            .collect::<Vec<_>>()
+            .map(TypeExpr::Struct);
+
+        let tuple = item
+            .clone()
+            .separated_by(just(','))
+            .at_least(1)
+            .collect::<Vec<_>>()
+            .map(|mut items| match items.len() {
+                1 => items.remove(0),
+                _ => TypeExpr::Tuple(items),
+            });
+
+        let table = choice((map, array, record, tuple))
+            .padded()
+            .delimited_by(just('{'), just('}'));
+
+        let union = just('?')
+            .ignore_then(
+                just('|')
+                    .ignore_then(ty.clone())
+                    .repeated()
+                    .at_least(2)
+                    .collect(),
+            )
+            .map(TypeExpr::Union);
+
+        let nullable = just('?')
+            .ignore_then(ty)
+            .map(|t| TypeExpr::Nullable(Box::new(t)));
+
+        choice((union, nullable, table, generic, ident.map(TypeExpr::Named)))
+    });
+
+    // optional {Type} after a parameter name; the braces are what set it apart from prose
+    let ty = just('{')
+        .rewind()
+        .ignore_then(type_expr.clone())
+        .padded_by(just(' ').repeated())
+        .or_not();
+
+    // The very first non-tag, non-empty line is the summary.
+
+    let summary = rest_of_line.filter(|s| !s.is_empty() && !s.starts_with('@'));
+
+    // Zero or more non-tag lines after the summary.
+
+    // Each line must consume something, or the repetition below never ends at the end of
+    // the comment: either its newline, or the last line's text.
+    let desc_line = choice((
+        rest_of_line
+            .filter(|s: &String| !s.starts_with('@'))
+            .then_ignore(just('\n')),
+        rest_of_line
+            .filter(|s: &String| !s.is_empty() && !s.starts_with('@'))
+            .then_ignore(end()),
+    ));
+
+    let description = desc_line
+        .repeated()
+        .at_least(1)
+        .collect::<Vec<_>>()
+        .map(|lines| lines.join("\n"))
+        .map(|s| s.trim().to_string())
+        .or_not();
+
+    // [opt], [opt=default], [optchain], [type=T], [1], [key=value] — comma separated
+    let modifier_value = none_of(",]")
+        .repeated()
+        .at_least(1)
+        .collect::<String>()
+        .map(|s| s.trim().to_string());
+
+    let modifier = choice((
+        text::int(10).map(|n: &str| TagModifier::ReturnGroup(n.parse().unwrap_or_default())),
+        just("optchain")
+            .ignore_then(just('=').ignore_then(modifier_value).or_not())
+            .map(|default| TagModifier::OptChain { default }),
+        just("opt")
+            .ignore_then(just('=').ignore_then(modifier_value).or_not())
+            .map(|default| TagModifier::Opt { default }),
+        just("type=").ignore_then(type_expr).map(TagModifier::Type),
+        ident
+            .then_ignore(just('='))
+            .then(modifier_value)
+            .map(|(key, value)| TagModifier::Custom { key, value }),
+    ))
+    .padded_by(just(' ').repeated());
+
+    let modifiers = modifier
+        .separated_by(just(','))
+        .collect::<Vec<_>>()
+        .delimited_by(just('['), just(']'))
+        .or_not()
+        .map(Option::unwrap_or_default);
+
+    let param = just("@param")
+        .ignore_then(modifiers.clone())
+        .then_ignore(just(' ').repeated().at_least(1))
+        .then(ident) // name
+        .then(ty) // optional {Type}
+        .then_ignore(just(' ').repeated())
+        .then(rest_of_line.or_not()) // description
+        .then_ignore(just('\n').or_not())
+        .map(|(((modifiers, name), param_type), description)| Param {
+            name,
+            // an explicit {Type} wins over a [type=T] modifier
+            param_type: param_type.or_else(|| {
+                modifiers.iter().find_map(|m| match m {
+                    TagModifier::Type(t) => Some(t.clone()),
+                    _ => None,
+                })
+            }),
+            description: description.filter(|s| !s.is_empty()),
+            modifiers,
+        });
+
+    let ret = just("@return")
+        .ignore_then(modifiers)
+        .then_ignore(just(' ').repeated())
+        .then(rest_of_line.or_not())
+        .then_ignore(just('\n').or_not())
+        .map(|(modifiers, description)| Return {
+            description: description.filter(|s| !s.is_empty()),
+            return_type: None,
+            group: modifiers.iter().find_map(|m| match m {
+                TagModifier::ReturnGroup(g) => Some(*g),
+                _ => None,
+            }),
+        });
+
+    let see = just("@see")
+        .then(just(' ').repeated().at_least(1))
+        .ignore_then(rest_of_line)
+        .then_ignore(just('\n').or_not())
+        .map(|target| See {
+            reference: Ref {
+                target,
+                display: None,
+            },
+        });
+
+    #[derive(Debug)]
+    enum Tag {
+        Param(Param),
+        Return(Return),
+        See(See),
+    }
+
+    let tag = choice((
+        param.map(Tag::Param),
+        ret.map(Tag::Return),
+        see.map(Tag::See),
+    ));
+
+    // A full document comment block
+    // Layout:
+    //   <summary>
+    //   [<description lines>]
+    //   [@tag ...]*
+
+    // A summary on its own is a complete doc comment.
+    summary
+        .then_ignore(just('\n').or_not())
+        .then(description)
+        .then(tag.repeated().collect::<Vec<_>>())
+        .map(|((summary, description), tags)| {
+            let mut doc = DocComment {
+                summary,
+                description,
+                ..DocComment::default()
+            };
+
+            for tag in tags {
+                match tag {
+                    Tag::Param(p) => doc.params.push(p),
+                    Tag::Return(r) => doc.returns.push(r),
+                    Tag::See(s) => doc.see.push(s),
+                }
+            }
+
+            Item {
+                name: doc.name.clone().unwrap_or_default(),
+                doc,
+                kind: ItemKind::Function,
+                location: None,
+            }
+        })
+}
+
+/// Whether a parameter must appear in `badger.toml`, taken from its `[opt]` modifiers.
+pub(crate) enum Presence<'a> {
+    Required,
+    /// `[opt]` — the producer receives `#f` when the parameter is left out.
+    Optional,
+    /// `[opt=value]` — the producer receives `value` when the parameter is left out.
+    Defaulted(&'a str),
+}
+
+impl<'a> Presence<'a> {
+    pub(crate) fn of(param: &'a Param) -> Self {
+        param
+            .modifiers
+            .iter()
+            .find_map(|m| match m {
+                TagModifier::Opt { default } | TagModifier::OptChain { default } => {
+                    Some(match default {
+                        Some(value) => Presence::Defaulted(value),
+                        None => Presence::Optional,
+                    })
+                }
+                _ => None,
+            })
+            .unwrap_or(Presence::Required)
+    }
+}
+
+/// Render a type expression back in LDoc syntax, for `list-producers`.
+pub(crate) fn type_name(ty: &TypeExpr) -> String {
+    let join =
+        |types: &[TypeExpr], sep: &str| types.iter().map(type_name).collect::<Vec<_>>().join(sep);
+
+    match ty {
+        TypeExpr::Named(n) => n.clone(),
+        TypeExpr::Nullable(t) => format!("?{}", type_name(t)),
+        TypeExpr::Union(types) => format!("?|{}", join(types, "|")),
+        TypeExpr::Tuple(types) => format!("{{{}}}", join(types, ", ")),
+        TypeExpr::Struct(fields) => {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|(name, t)| format!("{name}={}", type_name(t)))
+                .collect();
+            format!("{{{}}}", fields.join(", "))
+        }
+        TypeExpr::Array(t) => format!("{{{}, ...}}", type_name(t)),
+        TypeExpr::Map { key, value } => format!("{{[{}]={}}}", type_name(key), type_name(value)),
+        TypeExpr::Generic { name, args } => format!("{name}({})", join(args, ", ")),
+    }
+}
+
+/// Whether a type already admits "no value", so the generated field needs no extra `Option`.
+pub(crate) fn is_nullable(ty: &TypeExpr) -> bool {
+    match ty {
+        TypeExpr::Nullable(_) => true,
+        TypeExpr::Union(types) => types
+            .iter()
+            .any(|t| matches!(t, TypeExpr::Named(n) if n == "nil")),
+        TypeExpr::Generic { name, .. } => name.eq_ignore_ascii_case("option"),
+        _ => false,
+    }
+}
+
+/// The type names plugin authors may use in `@param {…}` annotations, and the Rust type
+/// each one becomes in generated code. Every Rust type here implements `params::IntoProducerArg`.
+pub(crate) const VOCABULARY: &[(&str, &str)] = &[
+    ("string", "String"),
+    ("int", "i64"),
+    ("float", "f64"),
+    ("bool", "bool"),
+    ("path", "std::path::PathBuf"),
+    ("glob", "crate::params::Glob"),
+    ("color", "crate::params::Color"),
+    ("duration", "crate::params::Duration"),
+    ("any", "toml::Value"),
+];
+
+/// LDoc spellings accepted in place of their vocabulary name.
+pub(crate) const ALIASES: &[(&str, &str)] =
+    &[("integer", "int"), ("number", "float"), ("boolean", "bool")];
+
+/// Resolve an alias to its vocabulary name, or `None` for names outside the vocabulary.
+pub(crate) fn canonical_type(name: &str) -> Option<&'static str> {
+    let name = ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map_or(name, |(_, canonical)| canonical);
+
+    VOCABULARY
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|(known, _)| *known)
+}
+
+/// Check that a type expression only uses the vocabulary and shapes producers can receive.
+pub(crate) fn check_type(ty: &TypeExpr) -> Result<(), String> {
+    match ty {
+        TypeExpr::Named(n) => canonical_type(n).map(|_| ()).ok_or_else(|| unknown_type(n)),
+        TypeExpr::Nullable(t) | TypeExpr::Array(t) => check_type(t),
+        TypeExpr::Union(types) => {
+            let rest: Vec<&TypeExpr> = types
+                .iter()
+                .filter(|t| !matches!(t, TypeExpr::Named(n) if n == "nil"))
+                .collect();
+            if rest.is_empty() {
+                return Err("a union needs at least one type besides `nil`".to_string());
+            }
+            rest.into_iter().try_for_each(check_type)
+        }
+        TypeExpr::Tuple(types) => {
+            if types.len() > 4 {
+                return Err("tuples may have at most 4 elements".to_string());
+            }
+            types.iter().try_for_each(check_type)
+        }
+        TypeExpr::Struct(fields) => fields.iter().try_for_each(|(_, t)| check_type(t)),
+        TypeExpr::Map { key, value } => check_map(key, value),
+        TypeExpr::Generic { name, args } => match (name.to_lowercase().as_str(), args.as_slice()) {
+            ("list" | "array" | "option", [t]) => check_type(t),
+            ("map", [k, v]) => check_map(k, v),
+            _ => Err(unknown_type(&type_name(ty))),
+        },
+    }
+}
+
+/// TOML table keys are always strings, so maps may only be keyed by `string`.
+fn check_map(key: &TypeExpr, value: &TypeExpr) -> Result<(), String> {
+    if !matches!(key, TypeExpr::Named(n) if n == "string") {
+        return Err(format!(
+            "map keys must be `string`, found `{}`",
+            type_name(key)
+        ));
+    }
+    check_type(value)
+}
+
+pub(crate) fn unknown_type(name: &str) -> String {
+    let known: Vec<&str> = VOCABULARY.iter().map(|(name, _)| *name).collect();
+    format!(
+        "unknown type `{name}`; expected one of {}, or list(T), map(string, T), option(T)",
+        known.join(", ")
+    )
+}
+
+/// Check an `[opt=value]` default against the parameter's type. Only scalar vocabulary types
+/// may carry a default.
+pub(crate) fn check_default(ty: Option<&TypeExpr>, value: &str) -> Result<(), String> {
+    let name = match ty {
+        None => return Ok(()),
+        Some(TypeExpr::Named(n)) => canonical_type(n).ok_or_else(|| unknown_type(n))?,
+        Some(other) => {
+            return Err(format!(
+                "only scalar types may have an [opt=…] default, not `{}`",
+                type_name(other)
+            ));
+        }
+    };
+
+    // The vocabulary's own parsers, so a default that would fail when the config loads
+    // fails the build instead.
+    let valid = match name {
+        "int" => value.parse::<i64>().is_ok(),
+        "float" => value.parse::<f64>().is_ok(),
+        "bool" => value.parse::<bool>().is_ok(),
+        "duration" => return value.parse::<Duration>().map(drop).map_err(invalid_default),
+        "color" => return value.parse::<Color>().map(drop).map_err(invalid_default),
+        "glob" => return value.parse::<Glob>().map(drop).map_err(invalid_default),
+        "path" => !value.is_empty(),
+        "string" => true,
+        _ => false,
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("default {value:?} is not a valid `{name}`"))
+    }
+}
+
+fn invalid_default(message: String) -> String {
+    format!("invalid default: {message}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The type of a lone `@param name {…}` line under a summary.
+    fn param_type(ty: &str) -> TypeExpr {
+        let doc = format!("Summary\n@param name {ty} description\n");
+        let item = parser().parse(&doc).into_result().unwrap();
+        item.doc.params[0].param_type.clone().unwrap()
+    }
+
+    fn named(name: &str) -> TypeExpr {
+        TypeExpr::Named(name.to_string())
+    }
+
+    #[test]
+    fn parses_type_expressions() {
+        assert_eq!(param_type("{int}"), named("int"));
+        assert_eq!(
+            param_type("{?int}"),
+            TypeExpr::Nullable(Box::new(named("int")))
+        );
+        assert_eq!(
+            param_type("{?|nil|string}"),
+            TypeExpr::Union(vec![named("nil"), named("string")])
+        );
+        assert_eq!(
+            param_type("{string, ...}"),
+            TypeExpr::Array(Box::new(named("string")))
+        );
+        assert_eq!(
+            param_type("{[string]=int}"),
+            TypeExpr::Map {
+                key: Box::new(named("string")),
+                value: Box::new(named("int")),
+            }
+        );
+        assert_eq!(
+            param_type("{int, string}"),
+            TypeExpr::Tuple(vec![named("int"), named("string")])
+        );
+        assert_eq!(
+            param_type("{a=int, b=string}"),
+            TypeExpr::Struct(vec![
+                ("a".to_string(), named("int")),
+                ("b".to_string(), named("string")),
+            ])
+        );
+        assert_eq!(
+            param_type("{list(int)}"),
+            TypeExpr::Generic {
+                name: "list".to_string(),
+                args: vec![named("int")],
+            }
+        );
+    }
+
+    #[test]
+    fn accepts_a_summary_on_its_own() {
+        for doc in ["Summary", "Summary\n"] {
+            let item = parser().parse(doc).into_result().unwrap();
+            assert_eq!(item.doc.summary, "Summary");
+            assert!(item.doc.params.is_empty());
+        }
+
+        for doc in ["Summary\nMore.", "Summary\nMore.\n"] {
+            let item = parser().parse(doc).into_result().unwrap();
+            assert_eq!(item.doc.description.as_deref(), Some("More."));
+        }
+    }
+
+    #[test]
+    fn extracts_the_doc_string() {
+        assert_eq!(
+            extract_quoted_string("(define a__doc__ \"Say \"hi\"\")"),
+            Some("Say \"hi\"")
+        );
+        assert_eq!(extract_quoted_string("(define a__doc__ #f)"), None);
+        assert_eq!(extract_quoted_string("\""), None);
+    }
+
+    #[test]
+    fn reads_presence_from_modifiers() {
+        let doc = "Summary\n@param a {int}\n@param[opt] b {int}\n@param[opt=3] c {int}\n";
+        let item = parser().parse(doc).into_result().unwrap();
+        let presence: Vec<Presence> = item.doc.params.iter().map(Presence::of).collect();
+
+        assert!(matches!(
+            presence.as_slice(),
+            [
+                Presence::Required,
+                Presence::Optional,
+                Presence::Defaulted("3")
+            ]
+        ));
+    }
+
+    #[test]
+    fn accepts_the_vocabulary_and_its_shapes() {
+        for ty in [
+            "{integer}",
+            "{?duration}",
+            "{?|nil|int|string}",
+            "{glob, ...}",
+            "{[string]=color}",
+            "{int, int, int, int}",
+            "{name=string, limit=?int}",
+            "{map(string, list(path))}",
+            "{option(bool)}",
+        ] {
+            assert_eq!(check_type(&param_type(ty)), Ok(()), "{ty}");
+        }
+    }
+
+    #[test]
+    fn rejects_types_producers_cannot_receive() {
+        for ty in [
+            "{widget}",
+            "{[int]=string}",
+            "{map(int, string)}",
+            "{int, int, int, int, int}",
+            "{?|nil|nil}",
+            "{set(int)}",
+        ] {
+            assert!(check_type(&param_type(ty)).is_err(), "{ty}");
+        }
+    }
+
+    #[test]
+    fn checks_defaults_against_their_type() {
+        let ok = [
+            ("{int}", "-3"),
+            ("{float}", "0.5"),
+            ("{bool}", "true"),
+            ("{string}", ""),
+            ("{path}", "src"),
+            ("{duration}", "1h30m"),
+            ("{color}", "#4c1"),
+            ("{glob}", "**/*.rs"),
+        ];
+        for (ty, value) in ok {
+            assert_eq!(
+                check_default(Some(&param_type(ty)), value),
+                Ok(()),
+                "{ty} {value}"
+            );
+        }
+
+        let bad = [
+            ("{int}", "three"),
+            ("{bool}", "yes"),
+            ("{path}", ""),
+            ("{duration}", "soon"),
+            ("{duration}", "-5"),
+            ("{color}", "not-a-color"),
+            ("{glob}", "[unclosed"),
+            ("{any}", "1"),
+            ("{string, ...}", "a"),
+        ];
+        for (ty, value) in bad {
+            assert!(
+                check_default(Some(&param_type(ty)), value).is_err(),
+                "{ty} {value}"
+            );
+        }
+    }
+
+    #[test]
+    fn finds_the_line_declaring_a_param() {
+        let source = ";;@doc\n;; Summary\n;; @param[opt] tag {string}\n;; @param label\n";
+        assert_eq!(param_line(source, "tag"), 3);
+        assert_eq!(param_line(source, "label"), 4);
+    }
+}
@@ -0,0 +1,20 @@
+(require "core")
+(provide cargo-field)
+
+(define (field->string value)
+  (cond [(list? value) (string-join (map field->string value) ", ")]
+        [(string? value) value]
+        [else (to-string value)]))
+
+;;@doc
+;; Any field of a crate's `[package]` table, such as `description` or `homepage`
+;; Fields inherited from `[workspace.package]` are resolved, and lists such as `keywords`
+;; are joined with commas.
+;; @param field {string} The package field to show, e.g. `edition` or `rust-version`
+;; @param[opt] package {string} The workspace member to read, when the project is a workspace
+;; @param[opt] label {string} The badge label, defaulting to the field name
+(define (cargo-field field package label)
+  (let ([value (hash-try-get (cargo-package package) field)])
+    (unless value
+      (error! "the package has no field" field))
+    (make-entry (or label field) (field->string value))))
@@ -0,0 +1,9 @@
+(require "core")
+(provide commit-count)
+
+;;@doc
+;; The number of commits on `HEAD`, or on another branch or revision range
+;; @param[opt=HEAD] rev {string} A revision such as `main`, or a range such as `v1.0.0..HEAD`
+;; @param[opt=commits] label {string} The badge label
+(define (commit-count rev label)
+  (make-entry label (format-count (git-commit-count rev))))
@@ -0,0 +1,14 @@
+(require "core")
+(provide commits-since-tag)
+
+;;@doc
+;; The number of commits on `HEAD` since a tag, by default the latest release
+;; Unless set, the label names the tag, e.g. `commits since v1.4.2`.
+;; @param[opt] tag {string} The tag to count from, instead of the highest semantic version
+;; @param[opt] label {string} The badge label
+(define (commits-since-tag tag label)
+  (let ([tag (or tag (git-latest-tag #f))])
+    (unless tag
+      (error! "the repository has no semantic-version tags"))
+    (make-entry (or label (string-append "commits since " tag))
+                (format-count (git-commit-count (string-append tag "..HEAD"))))))
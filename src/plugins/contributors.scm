@@ -0,0 +1,9 @@
+(require "core")
+(provide contributors)
+
+;;@doc
+;; The number of distinct commit authors on `HEAD`
+;; Authors are merged through the repository's `.mailmap`, if it has one.
+;; @param[opt=contributors] label {string} The badge label
+(define (contributors label)
+  (make-entry label (format-count (length (git-authors)))))
@@ -0,0 +1,30 @@
+(require "core")
+(provide coverage)
+
+(define (percent->string p)
+  (string-append (number->string (/ (round (* p 10)) 10)) "%"))
+
+(define (coverage-color p yellow green)
+  (cond [(>= p green) "#4c1"]
+        [(>= p yellow) "#dfb317"]
+        [else "#e05d44"]))
+
+;;@doc
+;; Test coverage from a CI report, e.g. `87.5%`, colored by threshold
+;; Reads `lcov.info`, Cobertura XML or `cargo llvm-cov --json` output. The badge is red
+;; below `yellow`, yellow below `green` and green from there up.
+;; @param[opt=lcov.info] path {path} The report, relative to the project root
+;; @param[opt=auto] format {string} `lcov`, `cobertura`, `llvm-cov`, or `auto` to detect it
+;; @param[opt=line] metric {string} Which coverage to show: `line` or `branch`
+;; @param[opt=60] yellow {float} The lowest percentage shown in yellow
+;; @param[opt=80] green {float} The lowest percentage shown in green
+;; @param[opt=coverage] label {string} The badge label
+(define (coverage path format metric yellow green label)
+  (let* ([report (read-coverage path format)]
+         [percent (cond [(equal? metric "line") (hash-ref report "line-percent")]
+                        [(equal? metric "branch") (hash-ref report "branch-percent")]
+                        [else (error! "unknown coverage metric" metric)])])
+    (unless percent
+      (error! "the coverage report has no branch data:" path))
+    (with-color (make-entry label (percent->string percent))
+                (coverage-color percent yellow green))))
@@ -0,0 +1,12 @@
+(require "core")
+(provide crate-categories)
+
+;;@doc
+;; The crates.io categories of a crate, joined with commas
+;; @param[opt] package {string} The workspace member to read, when the project is a workspace
+;; @param[opt=3] limit {int} Show at most this many categories
+(define (crate-categories package limit)
+  (let* ([categories (or (hash-try-get (cargo-package package) "categories") '())]
+         [shown (if (> (length categories) limit) (take categories limit) categories)])
+    (make-entry "categories"
+                (if (null? shown) "none" (string-join shown ", ")))))
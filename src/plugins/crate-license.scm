@@ -0,0 +1,37 @@
+(require "core")
+(provide crate-license)
+
+(define operators '("OR" "AND" "WITH"))
+
+;; Licenses common on crates.io, in their canonical SPDX spelling.
+(define known-licenses
+  '("MIT" "Apache-2.0" "BSD-2-Clause" "BSD-3-Clause" "ISC" "MPL-2.0" "Zlib" "Unlicense"
+    "0BSD" "CC0-1.0" "BSL-1.0" "LGPL-2.1" "LGPL-3.0" "GPL-2.0" "GPL-3.0" "AGPL-3.0"
+    "LLVM-exception"))
+
+(define (canonical-token token)
+  (let ([known (filter (lambda (id) (string-ci=? id token)) known-licenses)])
+    (cond [(member (string-upcase token) operators) (string-upcase token)]
+          [(pair? known) (car known)]
+          [else token])))
+
+;; Normalise a `license` field to an SPDX expression: the legacy `MIT/Apache-2.0` form
+;; becomes `MIT OR Apache-2.0`, and operators and well-known ids get their SPDX casing.
+(define (spdx-expression license)
+  (let* ([spaced (string-replace (string-replace (string-replace license "/" " OR ")
+                                                 "(" " ( ")
+                                 ")" " ) ")]
+         [tokens (map canonical-token (split-whitespace spaced))])
+    (string-replace (string-replace (string-join tokens " ") "( " "(") " )" ")")))
+
+;;@doc
+;; The license of a crate, as an SPDX expression such as `MIT OR Apache-2.0`
+;; A crate with only a `license-file` is shown as `custom`.
+;; @param[opt] package {string} The workspace member to read, when the project is a workspace
+(define (crate-license package)
+  (let* ([pkg (cargo-package package)]
+         [license (hash-try-get pkg "license")])
+    (make-entry "license"
+                (cond [license (spdx-expression license)]
+                      [(hash-try-get pkg "license-file") "custom"]
+                      [else "unlicensed"]))))
@@ -0,0 +1,11 @@
+(require "core")
+(provide crate-version)
+
+;;@doc
+;; The version of a crate, as published
+;; Versions inherited from `[workspace.package]` are resolved.
+;; @param[opt] package {string} The workspace member to read, when the project is a workspace
+;; @param[opt=v] prefix {string} Text shown before the version number
+(define (crate-version package prefix)
+  (let ([version (hash-try-get (cargo-package package) "version")])
+    (make-entry "version" (string-append prefix (or version "0.0.0")))))
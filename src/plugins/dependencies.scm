@@ -0,0 +1,58 @@
+(require "core")
+(provide dependencies)
+
+;; Lockfile dependency entries read `name`, `name version` or `name version (source)`.
+(define (dependency-name entry)
+  (car (split-whitespace entry)))
+
+(define (count-names names)
+  (foldl (lambda (name counts)
+           (hash-insert counts name (+ 1 (or (hash-try-get counts name) 0))))
+         (hash)
+         names))
+
+(define (source-kind package)
+  (let ([source (hash-try-get package "source")])
+    (cond [(not source) 'path]
+          [(starts-with? source "git+") 'git]
+          [else 'registry])))
+
+;;@doc
+;; Dependency statistics from the workspace's `Cargo.lock`, e.g. `42 (3 dup)`
+;; Counts every package in the lockfile except the workspace's own members. Direct dependencies
+;; are those a member depends on; duplicates are crates locked at more than one version.
+;; @param[opt=summary] metric {string} One of `summary`, `total`, `direct`, `duplicates`, `git` or `path`
+;; @param[opt=deps] label {string} The badge label
+(define (dependencies metric label)
+  (let* ([members (cargo-workspace-members)]
+         [member? (lambda (package) (and (member (hash-ref package "name") members)
+                                         (equal? (source-kind package) 'path)))]
+         [packages (or (hash-try-get (cargo-lock) "package") '())]
+         [deps (filter (lambda (package) (not (member? package))) packages)]
+         [direct (count-names
+                  (map dependency-name
+                       (apply append
+                              (map (lambda (package) (or (hash-try-get package "dependencies") '()))
+                                   (filter member? packages)))))]
+         [direct (filter (lambda (name) (not (member name members))) (hash-keys->list direct))]
+         [versions (count-names (map (lambda (package) (hash-ref package "name")) deps))]
+         [duplicates (length (filter (lambda (count) (> count 1)) (hash-values->list versions)))]
+         [of-kind (lambda (kind)
+                    (length (filter (lambda (package) (equal? (source-kind package) kind)) deps)))]
+         [total (length deps)])
+    (make-entry
+     label
+     (cond [(equal? metric "total") (number->string total)]
+           [(equal? metric "direct") (number->string (length direct))]
+           [(equal? metric "duplicates") (number->string duplicates)]
+           [(equal? metric "git") (number->string (of-kind 'git))]
+           [(equal? metric "path") (number->string (of-kind 'path))]
+           [(equal? metric "summary")
+            (let ([notes (filter (lambda (note) note)
+                                 (list (and (> duplicates 0) (string-append (number->string duplicates) " dup"))
+                                       (and (> (of-kind 'git) 0) (string-append (number->string (of-kind 'git)) " git"))
+                                       (and (> (of-kind 'path) 0) (string-append (number->string (of-kind 'path)) " path"))))])
+              (if (null? notes)
+                  (number->string total)
+                  (string-append (number->string total) " (" (string-join notes ", ") ")")))]
+           [else (error! "unknown metric" metric)]))))
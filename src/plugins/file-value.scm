@@ -0,0 +1,21 @@
+(require "core")
+(provide file-value)
+
+(define (badge-text value)
+  (cond [(string? value) value]
+        [(number? value) (number->string value)]
+        [(boolean? value) (if value "true" "false")]
+        [(datetime? value) (datetime->string value)]
+        [(list? value) (string-join (map badge-text value) ", ")]
+        [else (error! "the query selects a value that cannot be shown on a badge:" value)]))
+
+;;@doc
+;; One value out of a TOML, JSON or YAML file, e.g. `pyproject.toml`'s version
+;; Lists are shown comma-separated.
+;; @param file {path} The file, relative to the project root
+;; @param query {string} Where the value is, such as `tool.poetry.version`, `$.engines.node` or `authors[0]`
+;; @param[opt] label {string} The badge label, defaulting to the query
+;; @param[opt=auto] format {string} `toml`, `json`, `yaml`, or `auto` to go by the extension
+(define (file-value file query label format)
+  (make-entry (or label query)
+              (badge-text (data-ref (read-data file format) query))))
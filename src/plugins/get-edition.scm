@@ -1,11 +1,11 @@
 (require "core")
+(provide get-edition)
 
 ;;@doc
-;; Get the ID of the closed document
-;; @param cratee If it's a workspace, define the crate to search against
-(define (get-edition)
-  (let* ([content (file->string (car (read-dir ".")))]
-         [toml    (parse-toml content)]
-         [pkg     (hash-ref toml "package")]
-         [edition (hash-ref pkg "edition")])
-    (make-entry "EDITION" edition)))
+;; The Rust edition a crate is written in
+;; Editions inherited from `[workspace.package]` are resolved, and a crate that does not
+;; declare one is on 2015, as with Cargo.
+;; @param[opt] package {string} The workspace member to read, when the project is a workspace
+(define (get-edition package)
+  (let ([edition (hash-try-get (cargo-package package) "edition")])
+    (make-entry "EDITION" (or edition "2015"))))
@@ -0,0 +1,9 @@
+(require "core")
+(provide last-commit)
+
+;;@doc
+;; The date of the latest commit on `HEAD`, e.g. `2024-05-01`
+;; Read from the local repository; no network access is needed.
+;; @param[opt=last commit] label {string} The badge label
+(define (last-commit label)
+  (make-entry label (hash-ref (git-last-commit) "date")))
@@ -0,0 +1,10 @@
+(require "core")
+(provide latest-tag)
+
+;;@doc
+;; The highest semantic-version tag in the repository, e.g. `v1.4.2`
+;; Tags that are not semantic versions, with or without a leading `v`, are ignored.
+;; @param[opt=false] prereleases {bool} Whether tags such as `v2.0.0-rc.1` count
+;; @param[opt=release] label {string} The badge label
+(define (latest-tag prereleases label)
+  (make-entry label (or (git-latest-tag prereleases) "none")))
@@ -0,0 +1,30 @@
+(require "core")
+(provide lines-of-code)
+
+(define kinds '("code" "comment" "blank"))
+
+(define (lines-of counts kind)
+  (if (equal? kind "all")
+      (apply + (map (lambda (k) (hash-ref counts k)) kinds))
+      (hash-ref counts kind)))
+
+;;@doc
+;; Lines of code in the project, in total or for one language, e.g. `12.3k`
+;; Files ignored by `.gitignore` and hidden files are not counted.
+;; @param[opt=total] language {string} A language such as `Rust` or `Scheme`, or `total` for all of them
+;; @param[opt=code] kind {string} Which lines to count: `code`, `comment`, `blank` or `all`
+;; @param[opt] dir {path} Only count files under this directory of the project
+;; @param[opt] label {string} The badge label, defaulting to `lines` or the language name
+(define (lines-of-code language kind dir label)
+  (unless (or (equal? kind "all") (member kind kinds))
+    (error! "unknown kind of line" kind))
+  (let* ([by-language (count-lines dir)]
+         [total? (equal? language "total")]
+         [selected (filter (lambda (name) (or total? (string-ci=? name language)))
+                           (hash-keys->list by-language))])
+    (when (and (not total?) (null? selected))
+      (error! "no files found for language" language))
+    (make-entry
+     (or label (if total? "lines" (car selected)))
+     (format-count
+      (apply + (map (lambda (name) (lines-of (hash-ref by-language name) kind)) selected))))))
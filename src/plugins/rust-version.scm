@@ -0,0 +1,12 @@
+(require "core")
+(provide rust-version)
+
+;;@doc
+;; The minimum supported Rust version (MSRV) of a crate, from `rust-version`
+;; @param[opt] package {string} The workspace member to read, when the project is a workspace
+;; @param[opt=MSRV] label {string} The badge label
+(define (rust-version package label)
+  (let ([msrv (hash-try-get (cargo-package package) "rust-version")])
+    (unless msrv
+      (error! "the package does not declare a rust-version"))
+    (make-entry label (string-append msrv "+"))))
@@ -0,0 +1,19 @@
+(require "core")
+(provide tests)
+
+;;@doc
+;; Test results from a CI report, e.g. `412 passed, 2 failed, 5 ignored`
+;; Reads JUnit XML or the libtest JSON written by
+;; `cargo test -- -Z unstable-options --format json`. The badge is red if any test failed.
+;; @param path {path} The report, relative to the project root
+;; @param[opt=auto] format {string} `junit`, `libtest`, or `auto` to detect it
+;; @param[opt=tests] label {string} The badge label
+(define (tests path format label)
+  (let* ([results (read-test-results path format)]
+         [failed (hash-ref results "failed")])
+    (with-color
+     (make-entry label
+                 (string-append (number->string (hash-ref results "passed")) " passed, "
+                                (number->string failed) " failed, "
+                                (number->string (hash-ref results "ignored")) " ignored"))
+     (if (> failed 0) "#e05d44" "#4c1"))))
@@ -0,0 +1,419 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use steel::SteelVal;
+use steel::steel_vm::engine::Engine;
+use tracing::{info, warn};
+
+use crate::documentation::{Item, Param};
+use crate::error::{BadgerError, BadgerResult};
+use crate::params::{default_to_arg, toml_to_arg};
+use crate::plugin_doc::{
+    Presence, check_default, check_type, extract_doc, is_nullable, param_line,
+};
+use crate::steel_engine::{load_plugin, plugin_error};
+use crate::{BUILTIN_PLUGINS, Producer};
+
+/// Project-local plugins, relative to the project root. Every `*.scm` file is a producer
+/// whose entry point is named after the file.
+pub(crate) const PLUGIN_DIR: &str = ".badger/plugins";
+
+/// Where a producer was loaded from.
+#[derive(Debug, Clone)]
+pub(crate) enum Origin {
+    /// Compiled into the binary from the given file under `src/plugins`.
+    Builtin(&'static str),
+    /// Loaded at startup from the project's plugin directory.
+    Local(PathBuf),
+}
+
+/// A producer known to the registry, with its parsed `;;@doc` comment.
+#[derive(Debug, Clone)]
+pub(crate) struct ProducerSpec {
+    pub(crate) name: String,
+    pub(crate) doc: Option<Item>,
+    pub(crate) origin: Origin,
+}
+
+impl ProducerSpec {
+    pub(crate) fn summary(&self) -> &str {
+        self.doc.as_ref().map_or("", |item| &item.doc.summary)
+    }
+
+    pub(crate) fn params(&self) -> &[Param] {
+        self.doc.as_ref().map_or(&[], |item| &item.doc.params)
+    }
+
+    /// The plugin file, which is also the name of its Steel module.
+    pub(crate) fn file(&self) -> Cow<'_, str> {
+        match &self.origin {
+            Origin::Builtin(path) => Cow::Borrowed(path),
+            Origin::Local(path) => path.to_string_lossy(),
+        }
+    }
+}
+
+/// The `producer` table of a badge: the producer's name and whatever parameters it takes.
+/// Parameters are checked against the producer's declaration by [`Registry::resolve`].
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ProducerConfig {
+    pub(crate) name: String,
+    #[serde(flatten)]
+    pub(crate) params: toml::Table,
+}
+
+/// Every producer available to a project, built-in or local, keyed by name.
+#[derive(Debug, Default)]
+pub(crate) struct Registry {
+    producers: BTreeMap<String, ProducerSpec>,
+    /// Local plugins that failed to load, with the reason. Only the badges that use one
+    /// fail; the rest of the project is unaffected.
+    broken: BTreeMap<String, String>,
+}
+
+impl Registry {
+    /// Register the built-in producers, then load every plugin under `root`'s plugin
+    /// directory into `engine`. A local plugin shadows a built-in of the same name, even
+    /// when it fails to load.
+    pub(crate) fn load(engine: &mut Engine, root: Option<&Path>) -> BadgerResult<Self> {
+        let mut registry = Self::default();
+
+        for plugin in BUILTIN_PLUGINS {
+            let doc = extract_doc(engine, plugin.name, plugin.source, None)
+                .map_err(|e| BadgerError::Plugin(format!("{}: {e}", plugin.path)))?;
+
+            registry.insert(ProducerSpec {
+                name: plugin.name.to_string(),
+                doc,
+                origin: Origin::Builtin(plugin.path),
+            });
+        }
+
+        if let Some(root) = root {
+            for path in local_plugins(&root.join(PLUGIN_DIR))? {
+                let Some(name) = path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .map(str::to_string)
+                else {
+                    warn!(path = %path.display(), "skipping plugin with a non UTF-8 name");
+                    continue;
+                };
+
+                if let Err(e) = registry.load_local(engine, &name, &path) {
+                    let reason = match e {
+                        BadgerError::Plugin(message) => message,
+                        other => format!("{}: {other}", path.display()),
+                    };
+                    warn!(producer = %name, "cannot load local plugin: {reason}");
+                    registry.producers.remove(&name);
+                    registry.broken.insert(name, reason);
+                }
+            }
+        }
+
+        Ok(registry)
+    }
+
+    fn load_local(&mut self, engine: &mut Engine, name: &str, path: &Path) -> BadgerResult<()> {
+        let source = fs::read_to_string(path)?;
+        let doc = extract_doc(engine, name, &source, Some(path.to_path_buf()))
+            .map_err(|e| BadgerError::Plugin(format!("{}: {e}", path.display())))?;
+
+        for param in doc.iter().flat_map(|item| &item.doc.params) {
+            let checked = match (&param.param_type, Presence::of(param)) {
+                (_, Presence::Defaulted(value)) => check_default(param.param_type.as_ref(), value),
+                (Some(ty), _) => check_type(ty),
+                (None, _) => Ok(()),
+            };
+
+            checked.map_err(|message| {
+                BadgerError::Plugin(format!(
+                    "{}:{}: parameter `{}` of producer `{name}`: {message}",
+                    path.display(),
+                    param_line(&source, &param.name),
+                    param.name,
+                ))
+            })?;
+        }
+
+        let module = path.to_string_lossy();
+        load_plugin(engine, &module, name, &source)
+            .map_err(|e| plugin_error(engine, &module, e))?;
+
+        if self
+            .producers
+            .get(name)
+            .is_some_and(|p| matches!(p.origin, Origin::Builtin(_)))
+        {
+            info!(producer = %name, path = %path.display(), "local plugin shadows built-in producer");
+        }
+
+        info!(producer = %name, path = %path.display(), "loaded local plugin");
+
+        self.insert(ProducerSpec {
+            name: name.to_string(),
+            doc,
+            origin: Origin::Local(path.to_path_buf()),
+        });
+
+        Ok(())
+    }
+
+    fn insert(&mut self, spec: ProducerSpec) {
+        self.producers.insert(spec.name.clone(), spec);
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&ProducerSpec> {
+        self.producers.get(name)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &ProducerSpec> {
+        self.producers.values()
+    }
+
+    /// The arguments to call `config`'s entry point with, in declared order.
+    pub(crate) fn resolve(&self, config: &ProducerConfig) -> BadgerResult<Vec<SteelVal>> {
+        if let Some(reason) = self.broken.get(&config.name) {
+            return Err(BadgerError::Plugin(reason.clone()));
+        }
+        let spec = self
+            .get(&config.name)
+            .ok_or_else(|| BadgerError::Config(format!("unknown producer `{}`", config.name)))?;
+
+        for key in config.params.keys() {
+            if !spec.params().iter().any(|p| &p.name == key) {
+                warn!(producer = %spec.name, param = %key, "ignoring unknown parameter");
+            }
+        }
+
+        match spec.origin {
+            Origin::Builtin(_) => {
+                let mut table = config.params.clone();
+                table.insert("name".to_string(), toml::Value::String(config.name.clone()));
+
+                let producer = Producer::deserialize(toml::Value::Table(table))?;
+                Ok(producer.args()?)
+            }
+            Origin::Local(_) => self.resolve_local(spec, config),
+        }
+    }
+
+    /// Local plugins have no generated type, so their parameters are checked here against
+    /// the types they declare.
+    fn resolve_local(
+        &self,
+        spec: &ProducerSpec,
+        config: &ProducerConfig,
+    ) -> BadgerResult<Vec<SteelVal>> {
+        let params = spec.params();
+        let invalid = |param: &str, message: String| {
+            BadgerError::Config(format!(
+                "parameter `{param}` of producer `{}`: {message}",
+                spec.name
+            ))
+        };
+
+        params
+            .iter()
+            .map(|param| {
+                let ty = param.param_type.as_ref();
+                match (config.params.get(&param.name), Presence::of(param)) {
+                    (Some(value), _) => toml_to_arg(ty, value.clone()),
+                    (None, Presence::Defaulted(default)) => default_to_arg(ty, default),
+                    (None, Presence::Optional) => Ok(SteelVal::BoolV(false)),
+                    (None, Presence::Required) if ty.is_some_and(is_nullable) => {
+                        Ok(SteelVal::BoolV(false))
+                    }
+                    (None, Presence::Required) => Err("missing required parameter".to_string()),
+                }
+                .map_err(|message| invalid(&param.name, message))
+            })
+            .collect()
+    }
+}
+
+/// Every `*.scm` file in `dir`, sorted so load order does not depend on the filesystem.
+pub(crate) fn local_plugins(dir: &Path) -> BadgerResult<Vec<PathBuf>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "scm") && path.is_file() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::badger::Commands;
+    use crate::cache::Inputs;
+    use crate::steel_engine::setup;
+
+    const GREET: &str = r#"(provide greet)
+
+(define (shout text) (string-append text "!"))
+
+;;@doc
+;; Greets someone
+;; @param who {string} Who to greet
+;; @param[opt=1] times {integer} How often
+(define (greet who times)
+  (list "hello" (shout who) times))
+"#;
+
+    fn project(name: &str, plugins: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("badger-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join(PLUGIN_DIR)).unwrap();
+        for (file, source) in plugins {
+            fs::write(root.join(PLUGIN_DIR).join(file), source).unwrap();
+        }
+        root.canonicalize().unwrap()
+    }
+
+    fn load(root: &Path) -> (Engine, Registry) {
+        let mut engine = setup(root, &Commands::default(), &Inputs::default()).unwrap();
+        let registry = Registry::load(&mut engine, Some(root)).unwrap();
+        (engine, registry)
+    }
+
+    fn config(source: &str) -> ProducerConfig {
+        toml::from_str(source).unwrap()
+    }
+
+    #[test]
+    fn loads_local_plugins_and_shadows_built_ins() {
+        let shadow = "(provide crate-version)\n(define (crate-version) \"local\")\n";
+        let root = project(
+            "registry-local",
+            &[
+                ("greet.scm", GREET),
+                ("crate-version.scm", shadow),
+                ("notes.txt", ""),
+            ],
+        );
+        let (_, registry) = load(&root);
+        fs::remove_dir_all(&root).unwrap();
+
+        let greet = registry.get("greet").unwrap();
+        assert!(matches!(greet.origin, Origin::Local(_)));
+        assert_eq!(greet.summary(), "Greets someone");
+        assert_eq!(greet.params().len(), 2);
+        assert!(matches!(
+            registry.get("crate-version").unwrap().origin,
+            Origin::Local(_)
+        ));
+        assert!(registry.get("notes").is_none());
+        assert!(matches!(
+            registry.get("last-commit").unwrap().origin,
+            Origin::Builtin(_)
+        ));
+    }
+
+    #[test]
+    fn isolates_a_broken_plugin_to_its_own_badges() {
+        let root = project(
+            "registry-broken",
+            &[
+                ("greet.scm", GREET),
+                (
+                    "unclosed.scm",
+                    "(provide unclosed)\n(define (unclosed) \"x\"",
+                ),
+                ("hidden.scm", "(define (hidden) \"no provide\")\n"),
+            ],
+        );
+        let (_, registry) = load(&root);
+        fs::remove_dir_all(&root).unwrap();
+
+        for name in ["unclosed", "hidden"] {
+            let error = registry
+                .resolve(&config(&format!("name = {name:?}")))
+                .unwrap_err();
+            assert!(matches!(error, BadgerError::Plugin(_)), "{name}: {error}");
+        }
+        let error = registry
+            .resolve(&config("name = \"hidden\""))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("must `(provide hidden)`"), "{error}");
+        assert!(
+            registry
+                .resolve(&config("name = \"greet\"\nwho = \"x\""))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn exports_only_the_entry_point() {
+        let other = "(provide other)\n(define (shout text) text)\n(define (other) (shout \"x\"))\n";
+        let root = project(
+            "registry-modules",
+            &[("greet.scm", GREET), ("other.scm", other)],
+        );
+        let (mut engine, _) = load(&root);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            engine
+                .run("(greet \"a\" 2)")
+                .unwrap()
+                .last()
+                .unwrap()
+                .to_string(),
+            "(\"hello\" \"a!\" 2)"
+        );
+        assert_eq!(
+            engine.run("(other)").unwrap().last(),
+            Some(&SteelVal::StringV("x".into()))
+        );
+        assert!(engine.run("(shout \"a\")").is_err());
+    }
+
+    #[test]
+    fn resolves_parameters_in_declared_order() {
+        let root = project("registry-params", &[("greet.scm", GREET)]);
+        let (_, registry) = load(&root);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            registry
+                .resolve(&config("name = \"greet\"\ntimes = 3\nwho = \"a\""))
+                .unwrap(),
+            [SteelVal::StringV("a".into()), SteelVal::IntV(3)]
+        );
+        assert_eq!(
+            registry
+                .resolve(&config("name = \"greet\"\nwho = \"a\"\nunknown = true"))
+                .unwrap(),
+            [SteelVal::StringV("a".into()), SteelVal::IntV(1)]
+        );
+
+        let error = |source: &str| registry.resolve(&config(source)).unwrap_err().to_string();
+        assert!(error("name = \"greet\"").contains("`who`"));
+        assert!(error("name = \"greet\"\nwho = 1").contains("`who`"));
+        assert!(error("name = \"missing\"").contains("unknown producer"));
+        let wrong = error("name = \"cargo-field\"\nfield = 1");
+        assert!(wrong.contains("expected a string"), "{wrong}");
+        assert_eq!(
+            registry
+                .resolve(&config("name = \"cargo-field\"\nfield = \"edition\""))
+                .unwrap()[0],
+            SteelVal::StringV("edition".into())
+        );
+    }
+}
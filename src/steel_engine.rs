@@ -1,17 +1,230 @@
+use std::path::Path;
+
+use steel::SteelErr;
+use steel::parser::ast::ExprKind;
+use steel::parser::parser::Parser as SteelParser;
+use steel::rerrs::ErrorKind;
+use steel::rvals::SteelVal;
 use steel::steel_vm::{engine::Engine, register_fn::RegisterFn};
 
-use crate::{error::BadgerError, wrappers::toml::parse_toml};
+use crate::BUILTIN_PLUGINS;
+use crate::badger::Commands;
+use crate::cache::Inputs;
+use crate::error::BadgerError;
+use crate::host;
+use crate::plugin_doc::require_entry_point;
+use crate::wrappers;
+use crate::wrappers::json::{parse_json, steel_to_json};
+use crate::wrappers::xml::parse_xml;
+use crate::wrappers::yaml::parse_yaml;
+
+/// The module every plugin requires as `"core"`.
+pub(crate) const CORE: &str = include_str!("./core.scm");
+
+/// Built-in Steel modules that reach the OS even in a sandboxed engine: spawning processes
+/// and cloning git repositories. Plugins may not require them, and all their bindings are
+/// replaced.
+const DENIED_MODULES: &[&str] = &["steel/process", "steel/git"];
 
-pub(crate) fn setup() -> Result<Engine, BadgerError> {
-    let mut engine = Engine::new();
+/// Further bindings that reach the OS, wherever Steel exports them: the environment,
+/// stdin, loading code or dylibs from disk, and `Engine::new`, which builds an engine
+/// without the sandbox.
+const DENIED_BINDINGS: &[&str] = &[
+    "env-var",
+    "maybe-get-env-var",
+    "set-env-var!",
+    "steel-home-location",
+    "load",
+    "load-expanded",
+    "#%build-dylib",
+    "#%get-dylib",
+    "dump-profiler",
+    "stdin",
+    "read-to-string",
+    "Engine::new",
+    "Engine::clone",
+    "Engine::add-module",
+    "Engine::modules->list",
+    "Engine::raise_error",
+];
+
+/// An engine with the core module, host functions bound to the project at `root` and its
+/// `commands` policy, and every built-in plugin loaded. The host functions record what
+/// producers read in `inputs`.
+pub(crate) fn setup(
+    root: &Path,
+    commands: &Commands,
+    inputs: &Inputs,
+) -> Result<Engine, BadgerError> {
+    // A sandboxed engine still binds processes, git and the environment; `deny_os_access`
+    // removes those, so plugins reach the OS only through the host modules below.
+    let mut engine = Engine::new_sandboxed();
+    deny_os_access(&mut engine);
     engine.with_contracts(true);
 
-    let core = include_str!("./core.scm");
-    let plugins = include_str!(concat!(env!("OUT_DIR"), "/all-plugins.scm"));
+    engine.register_steel_module("core".to_string(), CORE.to_string());
+    wrappers::toml::register(&mut engine);
+    engine.register_fn("parse-json", parse_json);
+    engine.register_fn("parse-yaml", parse_yaml);
+    engine.register_fn("parse-xml", parse_xml);
+    engine.register_fn("steel->json", steel_to_json);
+    host::cargo::register(&mut engine, root, inputs);
+    host::command::register(&mut engine, root, commands, inputs);
+    host::data::register(&mut engine, root, inputs);
+    host::fs::register(&mut engine, root, inputs);
+    host::git::register(&mut engine, root, inputs);
+    host::loc::register(&mut engine, root, inputs);
+    host::reports::register(&mut engine, root, inputs);
 
-    engine.register_steel_module("core".to_string(), core.to_string());
-    engine.register_fn("parse-toml", parse_toml);
-    engine.run(plugins)?;
+    for plugin in BUILTIN_PLUGINS {
+        engine.register_steel_module(plugin.path.to_string(), plugin.source.to_string());
+        load_plugin(&mut engine, plugin.path, plugin.name, plugin.source)
+            .map_err(|e| plugin_error(&engine, plugin.path, e))?;
+    }
 
     Ok(engine)
 }
+
+/// Replace every binding of [`DENIED_MODULES`] and every [`DENIED_BINDINGS`] name with a
+/// function that fails: in the built-in modules that export it, at the top level, and in
+/// the `#%prim.` alias the compiler resolves builtins through. The names stay bound
+/// because Steel panics on a module lookup that misses.
+fn deny_os_access(engine: &mut Engine) {
+    let modules: Vec<_> = engine.builtin_modules().inner().values().cloned().collect();
+    for mut module in modules {
+        let whole = DENIED_MODULES.contains(&&*module.name());
+        for name in module.names() {
+            if whole || DENIED_BINDINGS.contains(&name.as_str()) {
+                module.register_value(&name, SteelVal::FuncV(denied));
+                engine.register_value(&name, SteelVal::FuncV(denied));
+                engine.register_value(&format!("#%prim.{name}"), SteelVal::FuncV(denied));
+            }
+        }
+    }
+}
+
+fn denied(_: &[SteelVal]) -> Result<SteelVal, SteelErr> {
+    Err(SteelErr::new(
+        ErrorKind::Generic,
+        "this primitive reaches outside the sandbox and is not available to plugins".to_string(),
+    ))
+}
+
+/// Load the plugin `source`, registered as the Steel module `module`, and bring its entry
+/// point `name` into the top level. A plugin that requires one of the
+/// [`DENIED_MODULES`] is refused before any of its code runs.
+pub(crate) fn load_plugin(
+    engine: &mut Engine,
+    module: &str,
+    name: &str,
+    source: &str,
+) -> Result<(), SteelErr> {
+    if let Some(denied) = required_builtins(source)
+        .into_iter()
+        .find(|required| DENIED_MODULES.contains(&required.as_str()))
+    {
+        return Err(SteelErr::new(
+            ErrorKind::Generic,
+            format!("plugins may not `(require-builtin {denied})`"),
+        ));
+    }
+
+    require_entry_point(engine, module, name, source)
+}
+
+/// The built-in modules `source` names in `(require-builtin …)` forms at its top level.
+fn required_builtins(source: &str) -> Vec<String> {
+    fn collect(expr: &ExprKind, found: &mut Vec<String>) {
+        match expr {
+            ExprKind::Begin(begin) => begin.exprs.iter().for_each(|e| collect(e, found)),
+            ExprKind::List(list)
+                if list
+                    .first_ident()
+                    .is_some_and(|i| i.resolve() == "require-builtin") =>
+            {
+                if let Some(module) = list.args.get(1).and_then(ExprKind::atom_identifier) {
+                    found.push(module.resolve().to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut found = Vec::new();
+    for expr in SteelParser::parse(source).unwrap_or_default() {
+        collect(&expr, &mut found);
+    }
+    found
+}
+
+/// Render a Steel error with the source snippet it points at, naming the plugin file.
+pub(crate) fn plugin_error(engine: &Engine, path: &str, error: SteelErr) -> BadgerError {
+    let report = engine
+        .raise_error_to_string(error.clone())
+        .unwrap_or_else(|| error.to_string());
+    BadgerError::Plugin(format!("{path}: {report}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine() -> Engine {
+        setup(Path::new("."), &Commands::default(), &Inputs::default()).unwrap()
+    }
+
+    fn load(engine: &mut Engine, source: &str) -> Result<(), SteelErr> {
+        engine.register_steel_module("test/escape".to_string(), source.to_string());
+        load_plugin(engine, "test/escape", "escape", source)
+    }
+
+    #[test]
+    fn refuses_plugins_requiring_os_modules() {
+        for source in [
+            "(require-builtin steel/process) (provide escape) (define (escape) 1)",
+            "(begin (require-builtin steel/git as g)) (provide escape) (define (escape) 1)",
+        ] {
+            let error = load(&mut engine(), source).unwrap_err();
+            assert!(
+                error.to_string().contains("may not `(require-builtin"),
+                "{error}"
+            );
+        }
+    }
+
+    #[test]
+    fn replaces_bindings_that_reach_the_os() {
+        for call in [
+            r#"(~> (command "cat" '("/etc/hostname")) (spawn-process))"#,
+            r#"(env-var "HOME")"#,
+            r#"(begin (require-builtin steel/meta as meta.) (meta.env-var "HOME"))"#,
+            "(Engine::new)",
+            "(read-to-string)",
+        ] {
+            let mut engine = engine();
+            let source = format!("(provide escape) (define (escape) {call})");
+            load(&mut engine, &source).unwrap();
+            let error = engine.run("(escape)").unwrap_err();
+            assert!(
+                error.to_string().contains("outside the sandbox"),
+                "{call}: {error}"
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_the_rest_of_the_language() {
+        let mut engine = engine();
+        let source = r#"
+            (require-builtin steel/time)
+            (provide escape)
+            (struct Point (x y))
+            (define (escape) (Point-x (Point (string-length "abc") (current-milliseconds))))
+        "#;
+        load(&mut engine, source).unwrap();
+        assert_eq!(
+            engine.run("(escape)").unwrap().last(),
+            Some(&SteelVal::IntV(3))
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use css_style::unit::{em, px};
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 use svg::Document;
 use svg::node::Text as TextNode;
 use svg::node::element::{ClipPath, Definitions, Group, Image, Polygon, Rectangle, Title};
@@ -50,9 +52,62 @@ pub struct BadgerOptions<'source> {
     pub label: Option<&'source str>,
     pub status: &'source str,
     pub icon: Option<&'source str>,
+    /// Tooltip text, defaulting to `label: status`.
+    pub title: Option<&'source str>,
     pub scale: Option<f64>,
 }
 
+/// How a badge is drawn.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Style {
+    /// Label and status on speckled, chamfered backgrounds.
+    #[default]
+    Speckled,
+    /// The status alone.
+    Bare,
+}
+
+impl std::str::FromStr for Style {
+    type Err = BadgerError;
+
+    fn from_str(s: &str) -> BadgerResult<Self> {
+        match s {
+            "speckled" => Ok(Self::Speckled),
+            "bare" => Ok(Self::Bare),
+            other => Err(BadgerError::Svg(format!(
+                "unknown badge style `{other}`; expected speckled or bare"
+            ))),
+        }
+    }
+}
+
+/// Draw a badge in the given style.
+pub fn render(style: Style, options: BadgerOptions) -> BadgerResult<Document> {
+    match style {
+        Style::Speckled => badgen(options),
+        Style::Bare => bare(options),
+    }
+}
+
+/// The speckles are drawn from what the badge shows, so an unchanged badge is redrawn
+/// exactly as it was and its file can be compared with a fresh render.
+fn speckle_seed(options: &BadgerOptions) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    for part in [
+        options.label,
+        Some(options.status),
+        options.primary_color,
+        options.secondary_color,
+        options.icon,
+        options.title,
+    ] {
+        hasher.update(part.unwrap_or_default().as_bytes());
+        hasher.update(b"\0");
+    }
+    *hasher.finalize().as_bytes()
+}
+
 fn create_accessible_text(label: &str, status: &str) -> String {
     format!("{label}: {status}")
 }
@@ -69,6 +124,7 @@ pub fn badgen(options: BadgerOptions) -> BadgerResult<Document> {
         return bare(BadgerOptions {
             status: options.status,
             primary_color: options.primary_color,
+            title: options.title,
             scale: options.scale,
             ..Default::default()
         });
@@ -105,7 +161,7 @@ pub fn badgen(options: BadgerOptions) -> BadgerResult<Document> {
         document = document.set("xmlns:xlink", "http://www.w3.org/1999/xlink");
     }
 
-    document = document.add(Title::new(accessible_text));
+    document = document.add(Title::new(options.title.unwrap_or(&accessible_text)));
 
     if let Some(icon) = options.icon {
         let image = Image::new()
@@ -132,7 +188,7 @@ pub fn badgen(options: BadgerOptions) -> BadgerResult<Document> {
     let status_width = status_end - status_start + (spacer / 2.0);
     let total_width: f32 = label_width + status_width + spacer;
 
-    let mut rng = rand::rng();
+    let mut rng = StdRng::from_seed(speckle_seed(&options));
     let seed: u32 = rng.random();
 
     let label_bg = create_speckle_group(
@@ -207,10 +263,6 @@ pub fn badgen(options: BadgerOptions) -> BadgerResult<Document> {
     let style = format!(r#"svg {{{style}}}"#);
     document = document.add(svg::node::element::Style::new(style));
 
-    let output = format!("{document:#}");
-    let output = output.replace("\n", "");
-    std::fs::write("./test.svg", output)?;
-
     Ok(document)
 }
 
@@ -234,7 +286,8 @@ pub fn bare(options: BadgerOptions) -> BadgerResult<Document> {
         .set("role", "img")
         .set("aria-label", sanitized_status.clone());
 
-    document = document.add(Title::new("").add(TextNode::new(sanitized_status.clone())));
+    let title = options.title.unwrap_or(sanitized_status);
+    document = document.add(Title::new("").add(TextNode::new(title)));
 
     Ok(document)
 }
@@ -3,4 +3,4 @@ mod filters;
 mod text;
 mod voronoi;
 
-pub use badge::{badgen, bare, BadgerOptions};
+pub use badge::{badgen, bare, render, BadgerOptions, Style};
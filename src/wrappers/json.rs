@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use steel::SteelVal;
+use steel::rvals::IntoSteelVal;
+
+use super::toml::{TomlDatetime, integer_to_steelval};
+
+pub fn parse_json(input: String) -> Result<SteelVal, String> {
+    let value: serde_json::Value = serde_json::from_str(&input).map_err(|e| e.to_string())?;
+    json_value_to_steelval(value)
+}
+
+/// Objects become hash maps and arrays lists, as with TOML; `null` becomes void. Integers
+/// too large for Steel are an error, as with TOML.
+pub fn json_value_to_steelval(value: serde_json::Value) -> Result<SteelVal, String> {
+    match value {
+        serde_json::Value::Null => Ok(SteelVal::Void),
+        serde_json::Value::Bool(b) => Ok(SteelVal::BoolV(b)),
+        serde_json::Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => integer_to_steelval(i.into()),
+            (None, Some(u)) => integer_to_steelval(u.into()),
+            (None, None) => Ok(SteelVal::NumV(n.as_f64().unwrap_or(f64::NAN))),
+        },
+        serde_json::Value::String(s) => Ok(SteelVal::StringV(s.into())),
+        serde_json::Value::Array(arr) => {
+            let vals: Result<Vec<SteelVal>, _> =
+                arr.into_iter().map(json_value_to_steelval).collect();
+            Ok(SteelVal::ListV(vals?.into()))
+        }
+        serde_json::Value::Object(object) => {
+            let map: Result<HashMap<String, SteelVal>, _> = object
+                .into_iter()
+                .map(|(k, v)| json_value_to_steelval(v).map(|v| (k, v)))
+                .collect();
+            map?.into_steelval().map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// The reverse of [`json_value_to_steelval`], for plugins that emit data: hashes become
+/// objects, lists and vectors arrays, datetimes strings and void `null`.
+pub fn steelval_to_json(value: &SteelVal) -> Result<serde_json::Value, String> {
+    match value {
+        SteelVal::Void => Ok(serde_json::Value::Null),
+        SteelVal::BoolV(b) => Ok(serde_json::Value::Bool(*b)),
+        SteelVal::IntV(i) => Ok(serde_json::Value::from(*i as i64)),
+        SteelVal::NumV(f) => serde_json::Number::from_f64(*f)
+            .map(serde_json::Value::Number)
+            .ok_or_else(|| format!("{f} has no JSON representation")),
+        SteelVal::StringV(s) => Ok(serde_json::Value::String(s.to_string())),
+        SteelVal::SymbolV(s) => Ok(serde_json::Value::String(s.to_string())),
+        SteelVal::CharV(c) => Ok(serde_json::Value::String(c.to_string())),
+        SteelVal::ListV(list) => list.iter().map(steelval_to_json).collect(),
+        SteelVal::VectorV(vector) => vector.iter().map(steelval_to_json).collect(),
+        SteelVal::MutableVector(vector) => vector.get().iter().map(steelval_to_json).collect(),
+        SteelVal::HashMapV(map) => map
+            .iter()
+            .map(|(k, v)| {
+                let key = match k {
+                    SteelVal::StringV(s) => s.to_string(),
+                    SteelVal::SymbolV(s) => s.to_string(),
+                    other => return Err(format!("hash key {other} is not a string or symbol")),
+                };
+                Ok((key, steelval_to_json(v)?))
+            })
+            .collect(),
+        SteelVal::Custom(_) => TomlDatetime::from_steelval(value)
+            .map(|dt| serde_json::Value::String(dt.0.to_string()))
+            .ok_or_else(|| format!("{value} has no JSON representation")),
+        other => Err(format!("{other} has no JSON representation")),
+    }
+}
+
+/// `steel->json`: `value` as compact JSON text.
+pub fn steel_to_json(value: SteelVal) -> Result<String, String> {
+    steelval_to_json(&value).map(|json| json.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_steel() {
+        let text = r#"{"name":"badger","tags":["a",null,true],"size":3,"ratio":0.5}"#;
+        let value = parse_json(text.to_string()).unwrap();
+        let back: serde_json::Value = serde_json::from_str(&steel_to_json(value).unwrap()).unwrap();
+
+        assert_eq!(
+            back,
+            serde_json::from_str::<serde_json::Value>(text).unwrap()
+        );
+        assert!(steel_to_json(SteelVal::NumV(f64::NAN)).is_err());
+        let numeric_key = HashMap::from([(1, 2)]).into_steelval().unwrap();
+        assert!(
+            steel_to_json(numeric_key)
+                .unwrap_err()
+                .contains("not a string")
+        );
+    }
+}
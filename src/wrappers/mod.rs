@@ -1 +1,4 @@
+pub mod json;
 pub mod toml;
+pub mod xml;
+pub mod yaml;
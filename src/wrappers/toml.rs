@@ -1,31 +1,71 @@
 use std::collections::HashMap;
 use steel::SteelVal;
-use steel::gc::Gc;
-use steel::rvals::{IntoSteelVal, SteelHashMap};
+use steel::rvals::{Custom, IntoSteelVal};
+use steel::steel_vm::engine::Engine;
 use steel::steel_vm::register_fn::RegisterFn;
+use toml::value::{Datetime, Offset};
 
-pub fn parse_toml(input: String) -> Result<HashMap<String, SteelVal>, String> {
-    let value: toml::Value = toml::from_str(&input).map_err(|e| e.to_string())?;
-    toml_to_steel(value)
+/// Register `parse-toml` and the accessors for the datetimes it produces:
+///
+/// - `(datetime? v)`
+/// - `(datetime-year dt)`, `datetime-month`, `datetime-day`: `#f` for a bare time.
+/// - `(datetime-hour dt)`, `datetime-minute`, `datetime-second`, `datetime-nanosecond`:
+///   `#f` for a bare date.
+/// - `(datetime-offset dt)`: minutes east of UTC, or `#f` for a local datetime.
+/// - `(datetime->unix dt)`: seconds since the Unix epoch, taking a missing time as
+///   midnight and a missing offset as UTC. Fails for a bare time.
+/// - `(datetime->string dt)`: the RFC 3339 text it was written as.
+pub fn register(engine: &mut Engine) {
+    engine.register_fn("parse-toml", parse_toml);
+    engine.register_fn("datetime?", |value: SteelVal| {
+        TomlDatetime::from_steelval(&value).is_some()
+    });
+    engine.register_fn("datetime-year", |dt: TomlDatetime| {
+        dt.0.date.map(|d| i64::from(d.year))
+    });
+    engine.register_fn("datetime-month", |dt: TomlDatetime| {
+        dt.0.date.map(|d| i64::from(d.month))
+    });
+    engine.register_fn("datetime-day", |dt: TomlDatetime| {
+        dt.0.date.map(|d| i64::from(d.day))
+    });
+    engine.register_fn("datetime-hour", |dt: TomlDatetime| {
+        dt.0.time.map(|t| i64::from(t.hour))
+    });
+    engine.register_fn("datetime-minute", |dt: TomlDatetime| {
+        dt.0.time.map(|t| i64::from(t.minute))
+    });
+    engine.register_fn("datetime-second", |dt: TomlDatetime| {
+        dt.0.time.map(|t| i64::from(t.second.unwrap_or(0)))
+    });
+    engine.register_fn("datetime-nanosecond", |dt: TomlDatetime| {
+        dt.0.time.map(|t| i64::from(t.nanosecond.unwrap_or(0)))
+    });
+    engine.register_fn("datetime-offset", |dt: TomlDatetime| dt.offset_minutes());
+    engine.register_fn("datetime->unix", |dt: TomlDatetime| dt.unix());
+    engine.register_fn("datetime->string", |dt: TomlDatetime| dt.0.to_string());
 }
 
-pub fn toml_to_steel(value: toml::Value) -> Result<HashMap<String, SteelVal>, String> {
-    match value {
-        toml::Value::Table(table) => {
-            let mut map = HashMap::new();
-            for (k, v) in table {
-                map.insert(k, toml_value_to_steelval(v)?);
-            }
-            Ok(map)
-        }
-        _ => Err("Top-level TOML must be a table".to_string()),
-    }
+/// A TOML document as a hash map, the same shape as every nested table.
+pub fn parse_toml(input: String) -> Result<SteelVal, String> {
+    let table: toml::Table = toml::from_str(&input).map_err(|e| e.to_string())?;
+    toml_value_to_steelval(toml::Value::Table(table))
+}
+
+/// An integer as a Steel integer, or an error when it does not fit one, rather than
+/// wrapping or rounding. Shared by every format plugins can parse.
+pub fn integer_to_steelval(i: i128) -> Result<SteelVal, String> {
+    isize::try_from(i)
+        .map(SteelVal::IntV)
+        .map_err(|_| format!("integer {i} does not fit a Steel integer"))
 }
 
+/// Tables become hash maps, arrays lists and datetimes [`TomlDatetime`]s. Integers are
+/// converted with [`integer_to_steelval`].
 pub fn toml_value_to_steelval(value: toml::Value) -> Result<SteelVal, String> {
     match value {
         toml::Value::String(s) => Ok(SteelVal::StringV(s.into())),
-        toml::Value::Integer(i) => Ok(SteelVal::IntV(i as isize)),
+        toml::Value::Integer(i) => integer_to_steelval(i.into()),
         toml::Value::Float(f) => Ok(SteelVal::NumV(f)),
         toml::Value::Boolean(b) => Ok(SteelVal::BoolV(b)),
         toml::Value::Array(arr) => {
@@ -40,6 +80,187 @@ pub fn toml_value_to_steelval(value: toml::Value) -> Result<SteelVal, String> {
                 .collect();
             map?.into_steelval().map_err(|e| e.to_string())
         }
-        toml::Value::Datetime(dt) => Ok(SteelVal::StringV(dt.to_string().into())),
+        toml::Value::Datetime(dt) => TomlDatetime(dt).into_steelval().map_err(|e| e.to_string()),
+    }
+}
+
+/// A TOML datetime, which may be an offset or local datetime, a local date or a local time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TomlDatetime(pub Datetime);
+
+impl Custom for TomlDatetime {
+    fn fmt(&self) -> Option<Result<String, std::fmt::Error>> {
+        Some(Ok(format!("#<datetime {}>", self.0)))
+    }
+}
+
+impl TomlDatetime {
+    pub fn from_steelval(value: &SteelVal) -> Option<Self> {
+        steel::rvals::FromSteelVal::from_steelval(value).ok()
+    }
+
+    fn offset_minutes(&self) -> Option<i64> {
+        self.0.offset.map(|offset| match offset {
+            Offset::Z => 0,
+            Offset::Custom { minutes } => i64::from(minutes),
+        })
+    }
+
+    fn unix(&self) -> Result<i64, String> {
+        let date = self
+            .0
+            .date
+            .ok_or_else(|| format!("{} is a time of day, not a point in time", self.0))?;
+        let seconds = self.0.time.map_or(0, |t| {
+            i64::from(t.hour) * 3600 + i64::from(t.minute) * 60 + i64::from(t.second.unwrap_or(0))
+        });
+
+        Ok(
+            days_from_civil(date.year.into(), date.month.into(), date.day.into()) * 86_400
+                + seconds
+                - self.offset_minutes().unwrap_or(0) * 60,
+        )
+    }
+}
+
+/// Days from 1970-01-01 to the given proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wrappers::{json::parse_json, yaml::parse_yaml};
+
+    fn datetime(text: &str) -> TomlDatetime {
+        TomlDatetime(text.parse().unwrap())
+    }
+
+    #[test]
+    fn refuses_integers_that_do_not_fit() {
+        assert_eq!(integer_to_steelval(-1), Ok(SteelVal::IntV(-1)));
+        assert_eq!(
+            integer_to_steelval(i64::MAX.into()),
+            Ok(SteelVal::IntV(isize::MAX))
+        );
+        assert!(integer_to_steelval(u64::MAX.into()).is_err());
+        assert!(integer_to_steelval(i128::MIN).is_err());
+
+        let too_big = u64::MAX.to_string();
+        assert!(parse_json(format!("[1, {too_big}]")).is_err());
+        assert!(parse_yaml(format!("- 1\n- {too_big}\n")).is_err());
+        assert_eq!(parse_json("1.5".to_string()), Ok(SteelVal::NumV(1.5)));
+    }
+
+    #[test]
+    fn reads_offset_datetimes() {
+        let utc = datetime("1979-05-27T07:32:00Z");
+        assert_eq!(utc.offset_minutes(), Some(0));
+        assert_eq!(utc.unix(), Ok(296_638_320));
+
+        let pacific = datetime("1979-05-27T07:32:00-08:00");
+        assert_eq!(pacific.offset_minutes(), Some(-480));
+        assert_eq!(pacific.unix(), Ok(296_638_320 + 8 * 3600));
+
+        assert_eq!(datetime("1969-12-31T23:59:59Z").unix(), Ok(-1));
+        assert_eq!(
+            datetime("2000-03-01T00:00:00+01:00").unix(),
+            Ok(951_865_200)
+        );
+    }
+
+    #[test]
+    fn reads_local_dates_and_times() {
+        let date = datetime("1979-05-27");
+        assert_eq!(date.offset_minutes(), None);
+        assert_eq!(date.unix(), Ok(296_611_200));
+
+        let local = datetime("1979-05-27T07:32:00.5");
+        assert_eq!(local.offset_minutes(), None);
+        assert_eq!(local.unix(), Ok(296_638_320));
+
+        let time = datetime("07:32:00");
+        assert!(time.unix().unwrap_err().contains("time of day"));
+    }
+
+    #[test]
+    fn refuses_out_of_range_values() {
+        for document in [
+            "d = 1979-13-01",
+            "d = 1979-02-30",
+            "t = 24:00:00",
+            "d = 1979-05-27T07:32:00+25:00",
+            "i = 9223372036854775808",
+        ] {
+            assert!(parse_toml(document.to_string()).is_err(), "{document}");
+        }
+        assert_eq!(
+            parse_toml("i = -9223372036854775808".to_string())
+                .map(|doc| doc.to_string().contains("-9223372036854775808")),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn exposes_datetime_accessors() {
+        let mut engine = Engine::new_sandboxed();
+        register(&mut engine);
+        let run = |engine: &mut Engine, expr: &str| {
+            let program = format!(
+                r#"(define doc (parse-toml "at = 1979-05-27T07:32:00.25-08:00\nday = 1979-05-27\nclock = 07:32:00"))
+                   {expr}"#
+            );
+            engine.run(program).unwrap().pop().unwrap()
+        };
+
+        for (expr, expected) in [
+            (
+                r#"(datetime-year (hash-ref doc "at"))"#,
+                SteelVal::IntV(1979),
+            ),
+            (r#"(datetime-day (hash-ref doc "day"))"#, SteelVal::IntV(27)),
+            (
+                r#"(datetime-hour (hash-ref doc "day"))"#,
+                SteelVal::BoolV(false),
+            ),
+            (
+                r#"(datetime-year (hash-ref doc "clock"))"#,
+                SteelVal::BoolV(false),
+            ),
+            (
+                r#"(datetime-minute (hash-ref doc "clock"))"#,
+                SteelVal::IntV(32),
+            ),
+            (
+                r#"(datetime-nanosecond (hash-ref doc "at"))"#,
+                SteelVal::IntV(250_000_000),
+            ),
+            (
+                r#"(datetime-offset (hash-ref doc "at"))"#,
+                SteelVal::IntV(-480),
+            ),
+            (
+                r#"(datetime-offset (hash-ref doc "day"))"#,
+                SteelVal::BoolV(false),
+            ),
+            (
+                r#"(datetime->unix (hash-ref doc "day"))"#,
+                SteelVal::IntV(296_611_200),
+            ),
+            (r#"(datetime? (hash-ref doc "at"))"#, SteelVal::BoolV(true)),
+            (r#"(datetime? "1979-05-27")"#, SteelVal::BoolV(false)),
+        ] {
+            assert_eq!(run(&mut engine, expr), expected, "{expr}");
+        }
+        assert_eq!(
+            run(&mut engine, r#"(datetime->string (hash-ref doc "at"))"#),
+            SteelVal::StringV("1979-05-27T07:32:00.25-08:00".into())
+        );
     }
 }
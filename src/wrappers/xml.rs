@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use steel::SteelVal;
+use steel::rvals::IntoSteelVal;
+
+/// The root element of an XML document. Each element becomes a hash map of its `name`,
+/// its `attributes` as a hash map, and its `children`: elements and non-blank text, in
+/// document order. Comments, processing instructions and namespaces are dropped.
+pub fn parse_xml(input: String) -> Result<SteelVal, String> {
+    let document = parse_document(&input)?;
+    xml_element_to_steelval(document.root_element())
+}
+
+/// Parse XML for plugins and for the report readers alike. A `DOCTYPE` is allowed, since
+/// Cobertura reports carry one; roxmltree never fetches external entities and refuses
+/// entity expansion that nests too deep or repeats too often.
+pub fn parse_document(input: &str) -> Result<roxmltree::Document<'_>, String> {
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..roxmltree::ParsingOptions::default()
+    };
+    roxmltree::Document::parse_with_options(input, options).map_err(|e| e.to_string())
+}
+
+pub fn xml_element_to_steelval(element: roxmltree::Node) -> Result<SteelVal, String> {
+    let attributes: HashMap<String, String> = element
+        .attributes()
+        .map(|a| (a.name().to_string(), a.value().to_string()))
+        .collect();
+
+    let mut children = Vec::new();
+    for child in element.children() {
+        if child.is_element() {
+            children.push(xml_element_to_steelval(child)?);
+        } else if let Some(text) = child.text().filter(|_| child.is_text()) {
+            let text = text.trim();
+            if !text.is_empty() {
+                children.push(SteelVal::StringV(text.into()));
+            }
+        }
+    }
+
+    HashMap::from([
+        (
+            "name".to_string(),
+            SteelVal::StringV(element.tag_name().name().into()),
+        ),
+        (
+            "attributes".to_string(),
+            attributes.into_steelval().map_err(|e| e.to_string())?,
+        ),
+        ("children".to_string(), SteelVal::ListV(children.into())),
+    ])
+    .into_steelval()
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(value: &SteelVal, key: &str) -> SteelVal {
+        let SteelVal::HashMapV(map) = value else {
+            panic!("expected a hash, got {value}");
+        };
+        map.get(&SteelVal::StringV(key.into())).unwrap().clone()
+    }
+
+    #[test]
+    fn reads_elements_after_a_doctype() {
+        let root = parse_xml(
+            "<!DOCTYPE coverage SYSTEM \"coverage-04.dtd\">\n\
+             <coverage line-rate=\"0.5\"><!-- note -->\n  <package>text</package>\n</coverage>"
+                .to_string(),
+        )
+        .unwrap();
+
+        assert_eq!(field(&root, "name"), SteelVal::StringV("coverage".into()));
+        assert_eq!(
+            field(&field(&root, "attributes"), "line-rate"),
+            SteelVal::StringV("0.5".into())
+        );
+        let SteelVal::ListV(children) = field(&root, "children") else {
+            panic!("expected a list of children");
+        };
+        let [package] = &children.iter().collect::<Vec<_>>()[..] else {
+            panic!("expected one child, got {children:?}");
+        };
+        assert_eq!(field(package, "name"), SteelVal::StringV("package".into()));
+        assert_eq!(field(package, "children").to_string(), "(\"text\")");
+    }
+
+    #[test]
+    fn refuses_entity_expansion_bombs() {
+        let mut bomb = "<!DOCTYPE r [<!ENTITY e0 \"lol\">".to_string();
+        for level in 1..10 {
+            let previous = format!("&e{};", level - 1).repeat(10);
+            bomb.push_str(&format!("<!ENTITY e{level} \"{previous}\">"));
+        }
+        bomb.push_str("]><r>&e9;</r>");
+
+        assert!(parse_document(&bomb).is_err());
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use steel::SteelVal;
+use steel::rvals::IntoSteelVal;
+
+use super::toml::integer_to_steelval;
+
+pub fn parse_yaml(input: String) -> Result<SteelVal, String> {
+    let value: serde_yaml_ng::Value = serde_yaml_ng::from_str(&input).map_err(|e| e.to_string())?;
+    yaml_value_to_steelval(value)
+}
+
+/// Mappings become hash maps and sequences lists, as with TOML; `null` becomes void.
+/// Scalar keys are used as strings, and tags are dropped. Integers too large for Steel are
+/// an error, as with TOML.
+pub fn yaml_value_to_steelval(value: serde_yaml_ng::Value) -> Result<SteelVal, String> {
+    match value {
+        serde_yaml_ng::Value::Null => Ok(SteelVal::Void),
+        serde_yaml_ng::Value::Bool(b) => Ok(SteelVal::BoolV(b)),
+        serde_yaml_ng::Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => integer_to_steelval(i.into()),
+            (None, Some(u)) => integer_to_steelval(u.into()),
+            (None, None) => Ok(SteelVal::NumV(n.as_f64().unwrap_or(f64::NAN))),
+        },
+        serde_yaml_ng::Value::String(s) => Ok(SteelVal::StringV(s.into())),
+        serde_yaml_ng::Value::Sequence(seq) => {
+            let vals: Result<Vec<SteelVal>, _> =
+                seq.into_iter().map(yaml_value_to_steelval).collect();
+            Ok(SteelVal::ListV(vals?.into()))
+        }
+        serde_yaml_ng::Value::Mapping(mapping) => {
+            let map: Result<HashMap<String, SteelVal>, _> = mapping
+                .into_iter()
+                .map(|(k, v)| Ok::<_, String>((yaml_key(k)?, yaml_value_to_steelval(v)?)))
+                .collect();
+            map?.into_steelval().map_err(|e| e.to_string())
+        }
+        serde_yaml_ng::Value::Tagged(tagged) => yaml_value_to_steelval(tagged.value),
+    }
+}
+
+fn yaml_key(key: serde_yaml_ng::Value) -> Result<String, String> {
+    match key {
+        serde_yaml_ng::Value::String(s) => Ok(s),
+        serde_yaml_ng::Value::Bool(b) => Ok(b.to_string()),
+        serde_yaml_ng::Value::Number(n) => Ok(n.to_string()),
+        serde_yaml_ng::Value::Null => Ok("null".to_string()),
+        other => Err(format!("unsupported mapping key {other:?}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uses_scalar_keys_as_strings_and_drops_tags() {
+        let value = parse_yaml("1: one\ntrue: yes\n~: nothing\ntagged: !custom 3\n".to_string());
+        let SteelVal::HashMapV(map) = value.unwrap() else {
+            panic!("expected a hash");
+        };
+        let get = |key: &str| map.get(&SteelVal::StringV(key.into())).cloned();
+
+        assert_eq!(get("1"), Some(SteelVal::StringV("one".into())));
+        assert_eq!(get("true"), Some(SteelVal::StringV("yes".into())));
+        assert_eq!(get("null"), Some(SteelVal::StringV("nothing".into())));
+        assert_eq!(get("tagged"), Some(SteelVal::IntV(3)));
+        assert!(parse_yaml("[a, b]: c\n".to_string()).is_err());
+    }
+}